@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE public.gecko_custody
+(
+    gecko_number bigint not null,
+    identity VARCHAR not null,
+    discord_user_id bigint,
+    custody VARCHAR not null,
+    updated_at timestamptz not null default now(),
+    CONSTRAINT gecko_custody_pkey PRIMARY KEY (gecko_number)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.gecko_custody
+    OWNER to postgres;
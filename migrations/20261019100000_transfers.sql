@@ -0,0 +1,22 @@
+-- Add migration script here
+CREATE TABLE public.transfers
+(
+    id bigserial not null,
+    gecko_number bigint not null,
+    identity VARCHAR not null,
+    kind VARCHAR not null,
+    from_discord_user_id bigint,
+    to_discord_user_id bigint,
+    from_address VARCHAR,
+    to_address VARCHAR not null,
+    txid VARCHAR not null,
+    status VARCHAR not null,
+    created_at timestamptz not null default now(),
+    confirmed_at timestamptz,
+    CONSTRAINT transfers_pkey PRIMARY KEY (id)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.transfers
+    OWNER to postgres;
@@ -0,0 +1,19 @@
+-- Add migration script here
+CREATE TABLE public.pending_signatures
+(
+    id bigserial not null,
+    gecko_number bigint not null,
+    identity VARCHAR not null,
+    discord_user_id bigint not null,
+    description VARCHAR not null,
+    partial_tx TEXT not null,
+    status VARCHAR not null,
+    completed_txid VARCHAR,
+    created_at timestamptz not null default now(),
+    CONSTRAINT pending_signatures_pkey PRIMARY KEY (id)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.pending_signatures
+    OWNER to postgres;
@@ -0,0 +1,48 @@
+-- Add migration script here
+CREATE TABLE public.listings
+(
+    txid VARCHAR not null,
+    gecko_number bigint not null,
+    identity VARCHAR not null,
+    discord_user_id bigint not null,
+    vrsc_address VARCHAR not null,
+    price bigint not null,
+    currency VARCHAR not null,
+    expiry_height bigint,
+    status VARCHAR not null,
+    channel_id bigint,
+    message_id bigint,
+    fill_txid VARCHAR,
+    created_at timestamptz not null default now(),
+    closed_at timestamptz,
+    CONSTRAINT listings_pkey PRIMARY KEY (txid)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.listings
+    OWNER to postgres;
+
+CREATE TABLE public.bids
+(
+    txid VARCHAR not null,
+    gecko_number bigint not null,
+    identity VARCHAR not null,
+    discord_user_id bigint not null,
+    vrsc_address VARCHAR not null,
+    price bigint not null,
+    currency VARCHAR not null,
+    expiry_height bigint,
+    status VARCHAR not null,
+    channel_id bigint,
+    message_id bigint,
+    fill_txid VARCHAR,
+    created_at timestamptz not null default now(),
+    closed_at timestamptz,
+    CONSTRAINT bids_pkey PRIMARY KEY (txid)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.bids
+    OWNER to postgres;
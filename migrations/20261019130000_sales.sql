@@ -0,0 +1,43 @@
+-- Add migration script here
+CREATE TABLE public.sales
+(
+    id bigserial not null,
+    offer_txid VARCHAR not null,
+    txid VARCHAR,
+    gecko_number bigint not null,
+    identity VARCHAR not null,
+    side VARCHAR not null,
+    price bigint not null,
+    currency VARCHAR not null,
+    seller_discord_user_id bigint,
+    buyer_discord_user_id bigint,
+    sold_at timestamptz not null default now(),
+    CONSTRAINT sales_pkey PRIMARY KEY (id),
+    CONSTRAINT sales_offer_txid_key UNIQUE (offer_txid)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.sales
+    OWNER to postgres;
+
+CREATE INDEX sales_gecko_number_idx ON public.sales (gecko_number);
+CREATE INDEX sales_sold_at_idx ON public.sales (sold_at);
+
+CREATE TABLE public.gecko_traits
+(
+    gecko_number bigint not null,
+    trait_type VARCHAR not null,
+    value VARCHAR not null,
+    CONSTRAINT gecko_traits_pkey PRIMARY KEY (gecko_number, trait_type)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.gecko_traits
+    OWNER to postgres;
+
+CREATE INDEX gecko_traits_trait_idx ON public.gecko_traits (trait_type, value);
+
+CREATE INDEX listings_status_idx ON public.listings (status);
+CREATE INDEX bids_status_idx ON public.bids (status);
@@ -0,0 +1,31 @@
+-- Add migration script here
+CREATE TABLE public.gecko_ownership
+(
+    gecko_number bigint not null,
+    identity VARCHAR not null,
+    identity_address VARCHAR not null,
+    primary_addresses TEXT[] not null,
+    minimum_signatures integer not null,
+    custody VARCHAR not null,
+    update_txid VARCHAR not null,
+    last_update_height bigint not null,
+    updated_at timestamptz not null default now(),
+    CONSTRAINT gecko_ownership_pkey PRIMARY KEY (gecko_number)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.gecko_ownership
+    OWNER to postgres;
+
+CREATE TABLE public.indexer_state
+(
+    name VARCHAR not null,
+    height bigint not null,
+    CONSTRAINT indexer_state_pkey PRIMARY KEY (name)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.indexer_state
+    OWNER to postgres;
@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE public.event_log
+(
+    id bigserial not null,
+    kind VARCHAR not null,
+    gecko_number bigint not null,
+    payload TEXT not null,
+    created_at timestamptz not null default now(),
+    CONSTRAINT event_log_pkey PRIMARY KEY (id)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.event_log
+    OWNER to postgres;
+
+CREATE INDEX event_log_gecko_number_idx ON public.event_log (gecko_number);
@@ -0,0 +1,23 @@
+-- Add migration script here
+CREATE TABLE public.webhook_outbox
+(
+    id bigserial not null,
+    url VARCHAR not null,
+    event VARCHAR not null,
+    payload TEXT not null,
+    signature VARCHAR not null,
+    status VARCHAR not null default 'pending',
+    attempts integer not null default 0,
+    last_error TEXT,
+    next_attempt_at timestamptz not null default now(),
+    created_at timestamptz not null default now(),
+    delivered_at timestamptz,
+    CONSTRAINT webhook_outbox_pkey PRIMARY KEY (id)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.webhook_outbox
+    OWNER to postgres;
+
+CREATE INDEX webhook_outbox_pending_idx ON public.webhook_outbox (next_attempt_at) WHERE status = 'pending';
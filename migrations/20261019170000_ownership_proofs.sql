@@ -0,0 +1,38 @@
+-- Add migration script here
+CREATE TABLE public.verification_challenges
+(
+    nonce VARCHAR not null,
+    gecko_number bigint not null,
+    discord_user_id bigint,
+    session_id VARCHAR,
+    created_at timestamptz not null default now(),
+    expires_at timestamptz not null,
+    CONSTRAINT verification_challenges_pkey PRIMARY KEY (nonce)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.verification_challenges
+    OWNER to postgres;
+
+CREATE TABLE public.ownership_proofs
+(
+    id bigserial not null,
+    gecko_number bigint not null,
+    identity VARCHAR not null,
+    discord_user_id bigint,
+    session_id VARCHAR,
+    signer VARCHAR not null,
+    signature TEXT not null,
+    verified_at timestamptz not null,
+    expires_at timestamptz not null,
+    CONSTRAINT ownership_proofs_pkey PRIMARY KEY (id)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.ownership_proofs
+    OWNER to postgres;
+
+CREATE INDEX ownership_proofs_discord_user_id_idx ON public.ownership_proofs (discord_user_id);
+CREATE INDEX ownership_proofs_session_id_idx ON public.ownership_proofs (session_id);
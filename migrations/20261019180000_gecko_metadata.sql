@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE TABLE public.gecko_metadata
+(
+    gecko_number bigint not null,
+    identity VARCHAR not null,
+    metadata_tx VARCHAR not null,
+    image_tx VARCHAR,
+    body TEXT not null,
+    confirmed boolean not null default false,
+    created_at timestamptz not null default now(),
+    confirmed_at timestamptz,
+    CONSTRAINT gecko_metadata_pkey PRIMARY KEY (gecko_number)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.gecko_metadata
+    OWNER to postgres;
+
+CREATE INDEX gecko_metadata_unconfirmed_idx ON public.gecko_metadata (gecko_number) WHERE NOT confirmed;
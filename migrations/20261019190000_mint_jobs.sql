@@ -0,0 +1,40 @@
+-- Add migration script here
+CREATE TABLE public.mint_jobs
+(
+    id bigserial not null,
+    discord_user_id bigint not null,
+    gecko_number bigint,
+    vrsc_address VARCHAR,
+    status VARCHAR not null default 'queued',
+    stage VARCHAR,
+    metadata_path VARCHAR,
+    image_path VARCHAR,
+    image_tx VARCHAR,
+    metadata_tx VARCHAR,
+    error VARCHAR,
+    created_at timestamptz not null default now(),
+    updated_at timestamptz not null default now(),
+    CONSTRAINT mint_jobs_pkey PRIMARY KEY (id),
+    CONSTRAINT mint_jobs_gecko_number_key UNIQUE (gecko_number)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.mint_jobs
+    OWNER to postgres;
+
+CREATE INDEX mint_jobs_status_idx ON public.mint_jobs (status);
+
+CREATE TABLE public.bot_flags
+(
+    name VARCHAR not null,
+    enabled boolean not null,
+    updated_by bigint,
+    updated_at timestamptz not null default now(),
+    CONSTRAINT bot_flags_pkey PRIMARY KEY (name)
+)
+
+TABLESPACE pg_default;
+
+ALTER TABLE public.bot_flags
+    OWNER to postgres;
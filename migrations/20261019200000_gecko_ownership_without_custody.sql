@@ -0,0 +1,4 @@
+-- Add migration script here
+-- the custody of a gecko is kept in gecko_custody only
+ALTER TABLE public.gecko_ownership
+    DROP COLUMN custody;
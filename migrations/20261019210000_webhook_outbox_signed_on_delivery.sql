@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE public.webhook_outbox
+    ALTER COLUMN signature DROP NOT NULL;
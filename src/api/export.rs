@@ -0,0 +1,60 @@
+// Writes the metadata of every indexed gecko to a directory, one `<number>.json` per gecko and a
+// `collection.json`, so it can be hosted anywhere.
+use sqlx::PgPool;
+use std::{error::Error, fs, path::Path};
+use tracing::{error, info};
+
+use super::render;
+use crate::{
+    configuration::Settings,
+    nft::formats::{Collection, MetadataFormat},
+};
+
+pub async fn export(
+    app_config: &Settings,
+    pool: &PgPool,
+    format: &str,
+    directory: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let format = format.parse::<MetadataFormat>()?;
+    let collection = Collection::load(&app_config.application.assets_dir);
+    fs::create_dir_all(directory)?;
+
+    if let Some(collection) = &collection {
+        fs::write(
+            directory.join("collection.json"),
+            serde_json::to_string_pretty(&collection.contract_metadata())?,
+        )?;
+    }
+
+    let geckos = sqlx::query!("SELECT gecko_number FROM gecko_ownership ORDER BY gecko_number")
+        .fetch_all(pool)
+        .await?;
+    info!(
+        "exporting {} geckos as {} to {}",
+        geckos.len(),
+        format.as_str(),
+        directory.display()
+    );
+
+    for gecko in geckos {
+        match render(
+            app_config,
+            pool,
+            collection.as_ref(),
+            gecko.gecko_number,
+            Some(format),
+        )
+        .await
+        {
+            Ok(metadata) => fs::write(
+                directory.join(format!("{}.json", gecko.gecko_number)),
+                serde_json::to_string_pretty(&metadata)?,
+            )?,
+            // e.g. metadata that is not confirmed on Arweave yet
+            Err(e) => error!("could not export gecko {}: {}", gecko.gecko_number, e),
+        }
+    }
+
+    Ok(())
+}
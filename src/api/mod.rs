@@ -0,0 +1,537 @@
+/*
+Read-only HTTP API
+*/
+// Serves what the bot knows about the geckos to web frontends and explorers, from the same tables
+// that the indexer and the marketplace keep up to date. Metadata is looked up on Verus and Arweave
+// like the `/list` command does, so clients do not need to decode the contentmap themselves.
+pub mod export;
+
+use axum::{
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::{error::Error, net::SocketAddr};
+use tracing::{error, info};
+use vrsc_rpc::{Auth, Client, RpcApi};
+
+use crate::{
+    bot::{
+        commands::gecko_identity_name,
+        custody::{self, Custody},
+        indexer,
+        metadata_cache::{self, MetadataError},
+        sales::{self, metadata_txid, MarketStats, SaleRecord, METADATA_VDXF_KEY},
+        verification::{self, Holder, Proof, VerificationError},
+    },
+    configuration::Settings,
+    nft::{
+        arweave::ArweaveError,
+        formats::{Collection, MetadataFormat, Subject},
+        metadata::NFTMetadata,
+    },
+};
+
+#[derive(Clone)]
+struct ApiState {
+    app_config: Settings,
+    pool: PgPool,
+    collection: Option<Collection>,
+}
+
+#[derive(Debug, Display)]
+enum ApiError {
+    #[display(fmt = "not found")]
+    NotFound,
+    BadRequest(String),
+    Database(sqlx::Error),
+    Verus(vrsc_rpc::Error),
+    Arweave(ArweaveError),
+    Metadata(serde_json::Error),
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        ApiError::Database(e)
+    }
+}
+
+impl From<vrsc_rpc::Error> for ApiError {
+    fn from(e: vrsc_rpc::Error) -> Self {
+        ApiError::Verus(e)
+    }
+}
+
+impl From<ArweaveError> for ApiError {
+    fn from(e: ArweaveError) -> Self {
+        ApiError::Arweave(e)
+    }
+}
+
+impl From<MetadataError> for ApiError {
+    fn from(e: MetadataError) -> Self {
+        match e {
+            MetadataError::Database(e) => ApiError::Database(e),
+            MetadataError::Arweave(e) => ApiError::Arweave(e),
+            MetadataError::Json(e) => ApiError::Metadata(e),
+        }
+    }
+}
+
+impl From<VerificationError> for ApiError {
+    fn from(e: VerificationError) -> Self {
+        match e {
+            VerificationError::UnknownGecko(_) => ApiError::NotFound,
+            VerificationError::Database(e) => ApiError::Database(e),
+            VerificationError::Verus(e) => ApiError::Verus(e),
+            other => ApiError::BadRequest(other.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(ref reason) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": reason })),
+                )
+                    .into_response()
+            }
+            // the details are for the logs only
+            _ => {
+                error!("API error: {:?}", &self);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+
+        (
+            status,
+            Json(serde_json::json!({ "error": status.canonical_reason() })),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct FormatQuery {
+    format: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Gecko {
+    gecko_number: i64,
+    identity: String,
+    identity_address: String,
+    owner: Vec<String>,
+    minimum_signatures: i32,
+    custody: String,
+    metadata_tx: Option<String>,
+    traits: IndexMap<String, String>,
+    // the cheapest open listing, in satoshis
+    listed_price: Option<u64>,
+    last_sale: Option<Sale>,
+    update_txid: String,
+    last_update_height: i64,
+}
+
+#[derive(Serialize)]
+struct Sale {
+    gecko_number: i64,
+    identity: String,
+    price: u64,
+    currency: String,
+    sold_at: String,
+}
+
+impl From<SaleRecord> for Sale {
+    fn from(sale: SaleRecord) -> Self {
+        Sale {
+            gecko_number: sale.gecko_number,
+            identity: sale.identity,
+            price: sale.price as u64,
+            currency: sale.currency,
+            sold_at: sale.sold_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OwnedGecko {
+    gecko_number: i64,
+    identity: String,
+    identity_address: String,
+}
+
+#[derive(Serialize)]
+struct TraitValue {
+    value: String,
+    count: i64,
+}
+
+#[derive(Deserialize)]
+struct ChallengeRequest {
+    gecko_number: i64,
+    session_id: String,
+}
+
+#[derive(Serialize)]
+struct ChallengeResponse {
+    nonce: String,
+    identity: String,
+    message: String,
+    expires_at: String,
+}
+
+#[derive(Deserialize)]
+struct VerifyRequest {
+    gecko_number: i64,
+    session_id: String,
+    // the identity of the gecko when left out
+    signer: Option<String>,
+    signature: String,
+}
+
+#[derive(Serialize)]
+struct ProofResponse {
+    gecko_number: i64,
+    identity: String,
+    signer: String,
+    verified_at: String,
+    expires_at: String,
+}
+
+impl From<Proof> for ProofResponse {
+    fn from(proof: Proof) -> Self {
+        ProofResponse {
+            gecko_number: proof.gecko_number,
+            identity: proof.identity,
+            signer: proof.signer,
+            verified_at: proof.verified_at.to_rfc3339(),
+            expires_at: proof.expires_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Stats {
+    geckos: i64,
+    owners: i64,
+    floor: Option<u64>,
+    listed: i64,
+    best_bid: Option<u64>,
+    bids: i64,
+    sales: i64,
+    volume: u64,
+    volume_day: u64,
+    volume_week: u64,
+    last_sale: Option<Sale>,
+}
+
+pub fn router(app_config: Settings, pool: PgPool) -> Router {
+    Router::new()
+        .route("/geckos/:number", get(gecko))
+        .route("/geckos/:number/metadata", get(metadata))
+        .route("/collection", get(collection))
+        .route("/owners/:address", get(owner))
+        .route("/traits", get(traits))
+        .route("/stats", get(stats))
+        .route("/verify/challenge", post(verify_challenge))
+        .route("/verify", post(verify))
+        .route("/verify/:session_id", get(session_proofs))
+        .layer(Extension(ApiState {
+            collection: Collection::load(&app_config.application.assets_dir),
+            app_config,
+            pool,
+        }))
+}
+
+pub async fn serve(app_config: Settings, pool: PgPool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let address: SocketAddr = app_config.api.listen_address.parse()?;
+    info!("API listening on {}", address);
+
+    axum::Server::bind(&address)
+        .serve(router(app_config, pool).into_make_service())
+        .await?;
+
+    Ok(())
+}
+
+// The arweave transaction of the metadata, from the contentmap of the identity of the gecko.
+fn metadata_tx(app_config: &Settings, gecko_number: i64) -> Result<Option<String>, ApiError> {
+    let client = match app_config.application.testnet {
+        true => Client::chain("vrsctest", Auth::ConfigFile, None),
+        false => Client::chain("VRSC", Auth::ConfigFile, None),
+    }?;
+
+    let identity = client.get_identity(&gecko_identity_name(app_config, gecko_number))?;
+
+    Ok(identity
+        .identity
+        .contentmap
+        .get(METADATA_VDXF_KEY)
+        .and_then(|hex_tx| metadata_txid(hex_tx).ok()))
+}
+
+async fn gecko(
+    Extension(state): Extension<ApiState>,
+    Path(number): Path<i64>,
+) -> Result<Json<Gecko>, ApiError> {
+    let ownership = indexer::get(&state.pool, number)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let traits = sqlx::query!(
+        "SELECT trait_type, value FROM gecko_traits WHERE gecko_number = $1 ORDER BY trait_type",
+        number
+    )
+    .fetch_all(&state.pool)
+    .await?
+    .into_iter()
+    .map(|row| (row.trait_type, row.value))
+    .collect();
+
+    let listing = sqlx::query!(
+        "SELECT MIN(price) AS price FROM listings WHERE status = 'open' AND gecko_number = $1",
+        number
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    // the indexer stores the custody of every gecko it indexes
+    let custody = custody::get(&state.pool, number)
+        .await?
+        .map(|gecko| gecko.custody)
+        .unwrap_or(Custody::Withdrawn);
+
+    let last_sale = sales::recent(&state.pool, Some(number), 1)
+        .await?
+        .into_iter()
+        .next()
+        .map(Sale::from);
+
+    Ok(Json(Gecko {
+        gecko_number: ownership.gecko_number,
+        identity: ownership.identity,
+        identity_address: ownership.identity_address,
+        owner: ownership.primary_addresses,
+        minimum_signatures: ownership.minimum_signatures,
+        custody: custody.as_str().to_string(),
+        metadata_tx: metadata_tx(&state.app_config, number)?,
+        traits,
+        listed_price: listing.price.map(|price| price as u64),
+        last_sale,
+        update_txid: ownership.update_txid,
+        last_update_height: ownership.last_update_height,
+    }))
+}
+
+// The metadata of a gecko as stored on Arweave, with the transaction it is stored in.
+async fn stored_metadata(
+    app_config: &Settings,
+    pool: &PgPool,
+    gecko_number: i64,
+) -> Result<(String, Value), ApiError> {
+    let tx = metadata_tx(app_config, gecko_number)?.ok_or(ApiError::NotFound)?;
+    let identity = gecko_identity_name(app_config, gecko_number);
+    let (raw_json, _) = metadata_cache::get(pool, gecko_number, &identity, &tx).await?;
+
+    Ok((tx, raw_json))
+}
+
+// The metadata in one of the formats of `MetadataFormat`, or as stored without `format`.
+async fn render(
+    app_config: &Settings,
+    pool: &PgPool,
+    collection: Option<&Collection>,
+    gecko_number: i64,
+    format: Option<MetadataFormat>,
+) -> Result<Value, ApiError> {
+    let (tx, raw_json) = stored_metadata(app_config, pool, gecko_number).await?;
+    let format = match format {
+        Some(format) => format,
+        None => return Ok(raw_json),
+    };
+
+    let ownership = indexer::get(pool, gecko_number)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let metadata = serde_json::from_value::<NFTMetadata>(raw_json).map_err(ApiError::Metadata)?;
+
+    Ok(format.render(
+        &metadata,
+        &Subject {
+            gecko_number,
+            identity: &ownership.identity,
+            identity_address: &ownership.identity_address,
+            metadata_tx: &tx,
+        },
+        collection,
+    ))
+}
+
+async fn metadata(
+    Extension(state): Extension<ApiState>,
+    Path(number): Path<i64>,
+    Query(query): Query<FormatQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let format = query
+        .format
+        .map(|format| format.parse::<MetadataFormat>())
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+
+    Ok(Json(
+        render(
+            &state.app_config,
+            &state.pool,
+            state.collection.as_ref(),
+            number,
+            format,
+        )
+        .await?,
+    ))
+}
+
+// The OpenSea `contractURI` document.
+async fn collection(Extension(state): Extension<ApiState>) -> Result<Json<Value>, ApiError> {
+    state
+        .collection
+        .as_ref()
+        .map(|collection| Json(collection.contract_metadata()))
+        .ok_or(ApiError::NotFound)
+}
+
+async fn owner(
+    Extension(state): Extension<ApiState>,
+    Path(address): Path<String>,
+) -> Result<Json<Vec<OwnedGecko>>, ApiError> {
+    let geckos = sqlx::query_as!(
+        OwnedGecko,
+        "SELECT gecko_number, identity, identity_address FROM gecko_ownership WHERE $1 = ANY(primary_addresses) ORDER BY gecko_number",
+        address
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(geckos))
+}
+
+// Every trait type with the number of geckos per value, most common first.
+async fn traits(
+    Extension(state): Extension<ApiState>,
+) -> Result<Json<IndexMap<String, Vec<TraitValue>>>, ApiError> {
+    let rows = sqlx::query!(
+        "SELECT trait_type, value, COUNT(*) AS count FROM gecko_traits GROUP BY trait_type, value ORDER BY trait_type, count DESC"
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut traits: IndexMap<String, Vec<TraitValue>> = IndexMap::new();
+    for row in rows {
+        traits.entry(row.trait_type).or_default().push(TraitValue {
+            value: row.value,
+            count: row.count.unwrap_or(0),
+        });
+    }
+
+    Ok(Json(traits))
+}
+
+async fn stats(Extension(state): Extension<ApiState>) -> Result<Json<Stats>, ApiError> {
+    let holders = sqlx::query!(
+        "SELECT COUNT(*) AS geckos, COUNT(DISTINCT primary_addresses) AS owners FROM gecko_ownership"
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    let MarketStats {
+        floor,
+        listed,
+        best_bid,
+        bids,
+        sales,
+        volume,
+        volume_day,
+        volume_week,
+        last_sale,
+    } = sales::stats(&state.pool).await?;
+
+    Ok(Json(Stats {
+        geckos: holders.geckos.unwrap_or(0),
+        owners: holders.owners.unwrap_or(0),
+        floor,
+        listed,
+        best_bid,
+        bids,
+        sales,
+        volume,
+        volume_day,
+        volume_week,
+        last_sale: last_sale.map(Sale::from),
+    }))
+}
+
+async fn verify_challenge(
+    Extension(state): Extension<ApiState>,
+    Json(request): Json<ChallengeRequest>,
+) -> Result<Json<ChallengeResponse>, ApiError> {
+    let challenge = verification::challenge(
+        &state.pool,
+        &state.app_config,
+        &Holder::Session(request.session_id),
+        request.gecko_number,
+    )
+    .await?;
+
+    Ok(Json(ChallengeResponse {
+        nonce: challenge.nonce,
+        identity: challenge.identity,
+        message: challenge.message,
+        expires_at: challenge.expires_at.to_rfc3339(),
+    }))
+}
+
+async fn verify(
+    Extension(state): Extension<ApiState>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<ProofResponse>, ApiError> {
+    let client = match state.app_config.application.testnet {
+        true => Client::chain("vrsctest", Auth::ConfigFile, None),
+        false => Client::chain("VRSC", Auth::ConfigFile, None),
+    }?;
+    let signer = request
+        .signer
+        .unwrap_or_else(|| gecko_identity_name(&state.app_config, request.gecko_number));
+
+    let proof = verification::verify(
+        &state.pool,
+        &state.app_config,
+        &client,
+        &Holder::Session(request.session_id),
+        request.gecko_number,
+        &signer,
+        &request.signature,
+    )
+    .await?;
+
+    Ok(Json(proof.into()))
+}
+
+// The geckos a web session proved to control.
+async fn session_proofs(
+    Extension(state): Extension<ApiState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<ProofResponse>>, ApiError> {
+    let proofs = verification::proofs(&state.pool, &Holder::Session(session_id)).await?;
+
+    Ok(Json(proofs.into_iter().map(ProofResponse::from).collect()))
+}
@@ -0,0 +1,176 @@
+// New geckos are announced in the channels under `announcements`, and their holder gets a DM about
+// their gecko and what they can do with it. A holder whose DMs are closed is mentioned in the
+// announcement channels instead.
+use serenity::{model::id::ChannelId, prelude::Context};
+use sqlx::PgPool;
+use std::error::Error;
+use tracing::{error, info};
+
+use crate::{
+    bot::{
+        sales,
+        utils::{
+            dm,
+            embeds::{self, GeckoView},
+        },
+    },
+    configuration::Settings,
+    nft::{metadata::NFTMetadata, VerusNFT},
+};
+
+/// Fills in the `{name}` placeholders of a template. Unknown placeholders are left as they are.
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |rendered, (name, value)| {
+            rendered.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+fn render_for(template: &str, app_config: &Settings, verus_nft: &VerusNFT) -> String {
+    render_gecko(
+        template,
+        app_config,
+        verus_nft.sequence as i64,
+        &format!("{}.{}@", verus_nft.sequence, &verus_nft.edition),
+        &format!("<@{}>", verus_nft.user_id),
+    )
+}
+
+fn render_gecko(
+    template: &str,
+    app_config: &Settings,
+    number: i64,
+    identity: &str,
+    member: &str,
+) -> String {
+    let number = number.to_string();
+
+    render(
+        template,
+        &[
+            ("series", app_config.announcements.series_name.as_str()),
+            ("number", number.as_str()),
+            ("identity", identity),
+            ("member", member),
+        ],
+    )
+}
+
+/// Posts the gecko in every announcement channel and returns in how many it was posted.
+pub(crate) async fn announce(ctx: &Context, app_config: &Settings, verus_nft: &VerusNFT) -> usize {
+    let title = render_for(&app_config.announcements.title, app_config, verus_nft);
+
+    post(
+        ctx,
+        app_config,
+        &GeckoView::from_verusnft(verus_nft, &title),
+    )
+    .await
+}
+
+async fn post(ctx: &Context, app_config: &Settings, view: &GeckoView) -> usize {
+    let mut posted = 0;
+
+    for channel_id in &app_config.announcements.channel_ids {
+        match ChannelId(*channel_id)
+            .send_message(&ctx.http, |m| m.embed(|e| embeds::render(e, view)))
+            .await
+        {
+            Ok(_) => posted += 1,
+            Err(e) => error!("could not announce in channel {}: {:?}", channel_id, e),
+        }
+    }
+
+    if app_config.announcements.channel_ids.is_empty() {
+        info!("no announcement channels are configured");
+    }
+
+    posted
+}
+
+/// Tells the holder of a new gecko about it, with a mention in the announcement channels when the
+/// DM cannot be delivered.
+pub(crate) async fn welcome(ctx: &Context, app_config: &Settings, verus_nft: &VerusNFT) {
+    let welcome = render_for(&app_config.announcements.welcome, app_config, verus_nft);
+    let e = match dm::try_send(ctx, verus_nft.user_id, welcome).await {
+        Ok(()) => return,
+        Err(e) => e,
+    };
+    info!(
+        "could not send the welcome DM to {}, mentioning them instead: {:?}",
+        verus_nft.user_id, e
+    );
+
+    let fallback = render_for(&app_config.announcements.fallback, app_config, verus_nft);
+    for channel_id in &app_config.announcements.channel_ids {
+        if let Err(e) = ChannelId(*channel_id).say(&ctx.http, &fallback).await {
+            error!("could not mention the holder in {}: {:?}", channel_id, e);
+        }
+    }
+}
+
+/// Announces a gecko again from its cached metadata, without a DM to its holder. Returns in how many
+/// channels it was posted, or nothing when there is no metadata stored for the gecko.
+pub async fn reannounce(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+    gecko_number: i64,
+) -> Result<Option<usize>, Box<dyn Error + Send + Sync>> {
+    let row = sqlx::query!(
+        "SELECT m.identity, m.metadata_tx, m.body, m.confirmed, o.primary_addresses, c.discord_user_id FROM gecko_metadata m JOIN gecko_ownership o ON o.gecko_number = m.gecko_number LEFT JOIN gecko_custody c ON c.gecko_number = m.gecko_number WHERE m.gecko_number = $1",
+        gecko_number
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let metadata = serde_json::from_str::<NFTMetadata>(&row.body)?;
+    // the member who deposited the gecko, or else the address that holds it
+    let owner = match (row.discord_user_id, row.primary_addresses.first()) {
+        (Some(discord_user_id), _) => format!("<@{}>", discord_user_id),
+        (None, Some(address)) => format!("`{}`", address),
+        (None, None) => return Err("the gecko has no primary address".into()),
+    };
+
+    let title = render_gecko(
+        &app_config.announcements.title,
+        app_config,
+        gecko_number,
+        &row.identity,
+        &owner,
+    );
+    let view = GeckoView {
+        title,
+        rank: sales::ranks(pool).await?.get(&gecko_number).copied(),
+        owner: Some(owner),
+        ..GeckoView::from_metadata(&metadata, &row.identity, &row.metadata_tx, row.confirmed)
+    }
+    .with_frequencies(&sales::trait_frequencies(pool, gecko_number).await?);
+
+    Ok(Some(post(ctx, app_config, &view).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        assert_eq!(
+            render(
+                "Introducing {series} #{number}, {series}!",
+                &[("series", "Goofy Geckos"), ("number", "12")]
+            ),
+            "Introducing Goofy Geckos #12, Goofy Geckos!"
+        );
+        assert_eq!(
+            render("{unknown} #{number}", &[("number", "1")]),
+            "{unknown} #1"
+        );
+    }
+}
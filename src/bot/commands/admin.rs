@@ -0,0 +1,295 @@
+// `/admin` is for the operators of the bot (see `is_operator`): minting for a member by hand,
+// retrying a mint job that failed, pausing and resuming the minting on join, the state of the mint
+// queue and the wallets, and announcing a gecko again. Minting itself is done by `bot::minting`.
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::application::{
+        command::CommandOptionType,
+        interaction::application_command::{
+            ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+        },
+    },
+    prelude::Context,
+};
+use std::path::Path;
+use tracing::error;
+use vrsc_rpc::{Client, RpcApi};
+
+use super::{
+    database_pool,
+    router::{edit_response, Acknowledge, CommandError, CommandResult, SlashCommand},
+};
+use crate::{
+    bot::{
+        announcements,
+        minting::{self, JobStatus},
+    },
+    configuration::Settings,
+    nft::arweave,
+};
+
+const FAILURES_SHOWN: i64 = 5;
+
+pub struct Admin;
+
+#[async_trait]
+impl SlashCommand for Admin {
+    fn name(&self) -> &'static str {
+        "admin"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Operate the bot")
+            .create_option(|option| {
+                option
+                    .name("mint")
+                    .description("Mint a Goofy Gecko for a member")
+                    .kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|sub_option| {
+                        sub_option
+                            .name("member")
+                            .description("The member to mint for")
+                            .kind(CommandOptionType::User)
+                            .required(true)
+                    })
+            })
+            .create_option(|option| {
+                option
+                    .name("retry")
+                    .description("Continue a failed mint job from the stage that failed")
+                    .kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|sub_option| {
+                        sub_option
+                            .name("job")
+                            .description("The id of the mint job")
+                            .kind(CommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(true)
+                    })
+            })
+            .create_option(|option| {
+                option
+                    .name("pause")
+                    .description("Stop minting for members who join")
+                    .kind(CommandOptionType::SubCommand)
+            })
+            .create_option(|option| {
+                option
+                    .name("resume")
+                    .description(
+                        "Mint for members who join again, and for those who joined while paused",
+                    )
+                    .kind(CommandOptionType::SubCommand)
+            })
+            .create_option(|option| {
+                option
+                    .name("status")
+                    .description("The mint queue, recent failures and the wallet balances")
+                    .kind(CommandOptionType::SubCommand)
+            })
+            .create_option(|option| {
+                option
+                    .name("announce")
+                    .description("Announce a Goofy Gecko again")
+                    .kind(CommandOptionType::SubCommand)
+                    .create_sub_option(|sub_option| {
+                        sub_option
+                            .name("number")
+                            .description("The number of the Goofy Gecko")
+                            .kind(CommandOptionType::Integer)
+                            .min_int_value(0)
+                            .required(true)
+                    })
+            })
+    }
+
+    fn admin_only(&self) -> bool {
+        true
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let subcommand = match command.data.options.first() {
+            Some(subcommand) => subcommand,
+            None => return Err(CommandError::message("Choose what to do")),
+        };
+        let pool = database_pool(ctx).await;
+
+        let content = match subcommand.name.as_str() {
+            "mint" => {
+                let user = match sub_option(subcommand, "member") {
+                    Some(CommandDataOptionValue::User(user, _)) => user,
+                    _ => return Err(CommandError::message("Choose a member to mint for")),
+                };
+                let id = minting::enqueue(&pool, user.id.0).await?;
+                tokio::spawn(minting::drain(ctx.clone(), app_config.clone(), pool));
+
+                format!("Minting for {} in job {}", user.tag(), id)
+            }
+            "retry" => {
+                let id = match sub_option(subcommand, "job") {
+                    Some(CommandDataOptionValue::Integer(id)) => *id,
+                    _ => return Err(CommandError::message("Choose a mint job")),
+                };
+                match minting::job(&pool, id).await? {
+                    Some(job) if job.status == JobStatus::Failed => {}
+                    Some(job) => {
+                        return Err(CommandError::message(format!(
+                            "Mint job {} is {}, only failed jobs can be retried",
+                            id, job.status
+                        )))
+                    }
+                    None => {
+                        return Err(CommandError::message(format!(
+                            "There is no mint job {}",
+                            id
+                        )))
+                    }
+                }
+                minting::requeue(&pool, id).await?;
+                tokio::spawn(minting::drain(ctx.clone(), app_config.clone(), pool));
+
+                format!("Retrying mint job {}", id)
+            }
+            "pause" => {
+                minting::set_paused(&pool, true, command.user.id.0).await?;
+
+                "Minting is paused, members who join are queued".to_string()
+            }
+            "resume" => {
+                minting::set_paused(&pool, false, command.user.id.0).await?;
+                tokio::spawn(minting::drain(ctx.clone(), app_config.clone(), pool));
+
+                "Minting is resumed".to_string()
+            }
+            "status" => status(app_config, client, &pool).await?,
+            "announce" => {
+                let number = match sub_option(subcommand, "number") {
+                    Some(CommandDataOptionValue::Integer(number)) => *number,
+                    _ => return Err(CommandError::message("Choose a Goofy Gecko")),
+                };
+                match announcements::reannounce(ctx, app_config, &pool, number).await {
+                    Ok(Some(0)) => {
+                        return Err(CommandError::message(format!(
+                        "Goofy Gecko #{} could not be posted, check `announcements.channel_ids`",
+                        number
+                    )))
+                    }
+                    Ok(Some(posted)) => {
+                        format!(
+                            "Goofy Gecko #{} is announced in {} channels",
+                            number, posted
+                        )
+                    }
+                    Ok(None) => {
+                        return Err(CommandError::message(format!(
+                            "Goofy Gecko #{} has no metadata stored yet",
+                            number
+                        )))
+                    }
+                    Err(e) => return Err(CommandError::Internal(e)),
+                }
+            }
+            other => {
+                error!("unknown /admin subcommand {}", other);
+                return Err(CommandError::message("Unknown subcommand"));
+            }
+        };
+
+        edit_response(ctx, command, content).await
+    }
+}
+
+fn sub_option<'a>(
+    subcommand: &'a CommandDataOption,
+    name: &str,
+) -> Option<&'a CommandDataOptionValue> {
+    subcommand
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.resolved.as_ref())
+}
+
+async fn status(
+    app_config: &Settings,
+    client: &Client,
+    pool: &sqlx::PgPool,
+) -> Result<String, CommandError> {
+    let paused = minting::is_paused(pool).await?;
+    let counts = minting::counts(pool).await?;
+    let failures = minting::recent_failures(pool, FAILURES_SHOWN).await?;
+
+    let mut lines = vec![format!(
+        "**Minting** {}",
+        match paused {
+            true => "paused",
+            false => "running",
+        }
+    )];
+
+    lines.push(match counts.is_empty() {
+        true => "**Jobs** none".to_string(),
+        false => format!(
+            "**Jobs** {}",
+            counts
+                .iter()
+                .map(|(status, count)| format!("{} {}", count, status))
+                .collect::<Vec<_>>()
+                .join(" · ")
+        ),
+    });
+
+    if !failures.is_empty() {
+        lines.push("**Recent failures**".to_string());
+        for job in failures {
+            lines.push(format!(
+                "`{}` <@{}>{} after {}: {}",
+                job.id,
+                job.discord_user_id,
+                job.gecko_number
+                    .map(|number| format!(" #{}", number))
+                    .unwrap_or_default(),
+                job.stage.as_deref().unwrap_or("nothing"),
+                job.error.as_deref().unwrap_or("unknown error")
+            ));
+        }
+    }
+
+    // a wallet that cannot be read should not hide the rest of the status
+    let vrsc = match client.call::<f64>("getbalance", &[]) {
+        Ok(balance) => format!("{}", balance),
+        Err(e) => {
+            error!("could not get the VRSC balance: {:?}", e);
+            "unavailable".to_string()
+        }
+    };
+    let keypair_location = Path::new(&app_config.application.ardrive_wallet_location);
+    let ar = match keypair_location.exists() {
+        true => match arweave::wallet_balance(keypair_location).await {
+            Ok(balance) => format!("{}", balance),
+            Err(e) => {
+                error!("could not get the AR balance: {:?}", e);
+                "unavailable".to_string()
+            }
+        },
+        false => "no wallet".to_string(),
+    };
+    lines.push(format!("**Wallets** {} VRSC · {} AR", vrsc, ar));
+
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,295 @@
+// `/revoke`, `/recover` and `/authorities`.
+// The bot can only sign a revoke or recover when its wallet controls the revocation or recovery
+// authority of the gecko, which is the case when the series policy points to an identity of the bot.
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::application::{
+        command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+    },
+    prelude::Context,
+};
+use std::str::FromStr;
+use tracing::{debug, error, info};
+use vrsc_rpc::{json::vrsc::Address, Client, RpcApi};
+
+use super::{
+    caller_controls, database_pool, gecko_identity_name, integer_option, is_admin,
+    router::{edit_response, Acknowledge, CommandError, CommandResult, SlashCommand},
+    string_option,
+};
+use crate::{configuration::Settings, nft::identity::Identity};
+
+// Resolves the `number` option to a gecko identity that the caller is allowed to act on:
+// admins can act on every gecko, users only on the geckos that the bot holds for them.
+async fn authorized_gecko(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    app_config: &Settings,
+    client: &Client,
+) -> Result<String, CommandError> {
+    let number = integer_option(command, "number")
+        .ok_or_else(|| CommandError::message("Which gecko do you want to update?"))?;
+
+    let identity_name = gecko_identity_name(app_config, number);
+    debug!("looking up {}", &identity_name);
+
+    let identity = client.get_identity(&identity_name).map_err(|e| {
+        debug!("{:?}", e);
+        CommandError::message("Identity not found, likely not confirmed on Verus")
+    })?;
+
+    if is_admin(command) {
+        return Ok(identity_name);
+    }
+
+    let pool = database_pool(ctx).await;
+    if caller_controls(
+        &pool,
+        command.user.id.0,
+        &identity.identity.primaryaddresses,
+    )
+    .await
+    {
+        Ok(identity_name)
+    } else {
+        Err(CommandError::message(format!(
+            "You are not the owner of `{}`",
+            identity_name
+        )))
+    }
+}
+
+pub struct Revoke;
+
+#[async_trait]
+impl SlashCommand for Revoke {
+    fn name(&self) -> &'static str {
+        "revoke"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Revoke a Goofy Gecko, for example when its keys are lost")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to revoke")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let identity_name = authorized_gecko(ctx, command, app_config, client).await?;
+
+        let txid =
+            Identity::revoke(&identity_name, app_config.application.testnet).map_err(|e| {
+                error!("could not revoke {}: {:?}", &identity_name, e);
+                CommandError::message(format!(
+                    "Could not revoke `{}`, the bot might not control its revocation authority.",
+                    identity_name
+                ))
+            })?;
+
+        info!("{} revoked {}", command.user.tag(), &identity_name);
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "`{}` is revoked. Use `/recover` to give it new primary addresses. (txid: {})",
+                identity_name, txid
+            ),
+        )
+        .await
+    }
+}
+
+pub struct Recover;
+
+#[async_trait]
+impl SlashCommand for Recover {
+    fn name(&self) -> &'static str {
+        "recover"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Recover a revoked Goofy Gecko to a new address")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to recover")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("address")
+                    .description("The R-address that will control the Goofy Gecko")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let address = string_option(command, "address")
+            .and_then(|address| Address::from_str(address).ok())
+            .ok_or_else(|| CommandError::message("That is not a valid R-address"))?;
+
+        let identity_name = authorized_gecko(ctx, command, app_config, client).await?;
+
+        let mut recovery = Identity::recover(&identity_name);
+        recovery
+            .testnet(app_config.application.testnet)
+            .add_address(&address);
+
+        recovery
+            .validate()
+            .map_err(|e| CommandError::message(format!("Invalid recovery: {}", e)))?;
+
+        let txid = recovery.update().await.map_err(|e| {
+            error!("could not recover {}: {:?}", &identity_name, e);
+            CommandError::message(format!(
+                "Could not recover `{}`, the bot might not control its recovery authority.",
+                identity_name
+            ))
+        })?;
+
+        info!(
+            "{} recovered {} to {}",
+            command.user.tag(),
+            &identity_name,
+            &address
+        );
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "`{}` is recovered to `{}`. (txid: {})",
+                identity_name, address, txid
+            ),
+        )
+        .await
+    }
+}
+
+pub struct Authorities;
+
+#[async_trait]
+impl SlashCommand for Authorities {
+    fn name(&self) -> &'static str {
+        "authorities"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Set the revoke and recover identities of your Goofy Gecko")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to update")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("revoke")
+                    .description("The identity that can revoke, e.g. `myname@`")
+                    .kind(CommandOptionType::String)
+                    .required(false)
+            })
+            .create_option(|option| {
+                option
+                    .name("recover")
+                    .description("The identity that can recover, e.g. `myname@`")
+                    .kind(CommandOptionType::String)
+                    .required(false)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let revocation = string_option(command, "revoke");
+        let recovery = string_option(command, "recover");
+
+        if revocation.is_none() && recovery.is_none() {
+            return Err(CommandError::message(
+                "Give a `revoke` identity, a `recover` identity or both",
+            ));
+        }
+
+        let identity_name = authorized_gecko(ctx, command, app_config, client).await?;
+
+        let mut update = Identity::update(&identity_name);
+        update.testnet(app_config.application.testnet);
+
+        if let Some(authority) = revocation {
+            update.revocation_authority(authority);
+        }
+
+        if let Some(authority) = recovery {
+            update.recovery_authority(authority);
+        }
+
+        update
+            .validate()
+            .map_err(|e| CommandError::message(format!("Invalid update: {}", e)))?;
+
+        let txid = update.update().await.map_err(|e| {
+            error!(
+                "could not update authorities of {}: {:?}",
+                &identity_name, e
+            );
+            CommandError::message(format!("Could not update `{}`: {}", identity_name, e))
+        })?;
+
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "The authorities of `{}` will be updated once the transaction confirms. (txid: {})",
+                identity_name, txid
+            ),
+        )
+        .await
+    }
+}
@@ -0,0 +1,331 @@
+// `/coown` and `/cosign`
+// A co-owned gecko has the address the bot keeps for the user and an address of the user's own
+// wallet as primary addresses. With 1-of-2 either of them can update the gecko, with 2-of-2 the bot
+// signs first and the user completes the transaction in their own wallet.
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::{
+        application::{
+            command::CommandOptionType,
+            interaction::application_command::ApplicationCommandInteraction,
+        },
+        channel::AttachmentType,
+    },
+    prelude::Context,
+};
+use sqlx::PgPool;
+use std::{borrow::Cow, str::FromStr};
+use tracing::{debug, error, info};
+use vrsc_rpc::{json::vrsc::Address, Client, RpcApi};
+
+use super::{
+    caller_controls, database_pool, gecko_identity_name, integer_option,
+    router::{edit_response, Acknowledge, CommandError, CommandResult, SlashCommand},
+    string_option,
+};
+use crate::{
+    bot::{cosign, utils::database},
+    configuration::Settings,
+    nft::identity::{Identity, IdentityUpdateBuilder},
+};
+
+// longer transactions are sent as a file, to stay under the message limit of Discord
+const MAX_INLINE_TRANSACTION_LENGTH: usize = 1500;
+
+/// Signs the update with the keys of the bot and gives the half-signed transaction to the user, in
+/// the response of the deferred command.
+pub(crate) async fn request_signature(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    pool: &PgPool,
+    gecko_number: i64,
+    identity_name: &str,
+    update: &IdentityUpdateBuilder,
+    description: &str,
+) -> CommandResult {
+    let partial_tx = update.partially_signed().await.map_err(|e| {
+        error!("could not sign update of {}: {:?}", identity_name, e);
+        CommandError::message(format!(
+            "Could not sign the update of `{}`: {}",
+            identity_name, e
+        ))
+    })?;
+
+    let id = cosign::store(
+        pool,
+        gecko_number,
+        identity_name,
+        command.user.id.0,
+        description,
+        &partial_tx,
+    )
+    .await?;
+
+    let instructions = format!(
+        "The bot signed the update to {}. Complete it by signing it in your own wallet:\n\
+        `signrawtransaction <transaction>`\n\
+        Then send the signed transaction back with `/cosign id:{} transaction:<signed transaction>` \
+        or broadcast it yourself with `sendrawtransaction`. \
+        The transaction expires after about 20 blocks.",
+        description, id
+    );
+
+    // the command is deferred, a transaction that does not fit in the response follows as a file
+    if partial_tx.len() <= MAX_INLINE_TRANSACTION_LENGTH {
+        return edit_response(
+            ctx,
+            command,
+            format!("{}\n```{}```", instructions, partial_tx),
+        )
+        .await;
+    }
+
+    edit_response(ctx, command, instructions).await?;
+    command
+        .create_followup_message(&ctx.http, |message| {
+            message
+                .add_file(AttachmentType::Bytes {
+                    data: Cow::from(partial_tx.as_bytes()),
+                    filename: format!("update-{}.hex", id),
+                })
+                .ephemeral(true)
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub struct Coown;
+
+#[async_trait]
+impl SlashCommand for Coown {
+    fn name(&self) -> &'static str {
+        "coown"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Co-own your Goofy Gecko with an address of your own wallet")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to co-own")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("address")
+                    .description("An R-address of your own wallet")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("mode")
+                    .description("Whether either of you or both need to sign")
+                    .kind(CommandOptionType::String)
+                    .add_string_choice("1-of-2 (either can act)", "1of2")
+                    .add_string_choice("2-of-2 (both must sign)", "2of2")
+                    .required(true)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let number = integer_option(command, "number")
+            .ok_or_else(|| CommandError::message("Which gecko do you want to co-own?"))?;
+        let user_address = string_option(command, "address")
+            .and_then(|address| Address::from_str(address).ok())
+            .ok_or_else(|| CommandError::message("That is not a valid R-address"))?;
+
+        let minimum_signatures = match string_option(command, "mode") {
+            Some("2of2") => 2,
+            _ => 1,
+        };
+
+        let identity_name = gecko_identity_name(app_config, number);
+        let identity = client.get_identity(&identity_name).map_err(|e| {
+            debug!("{:?}", e);
+            CommandError::message("Identity not found, likely not confirmed on Verus")
+        })?;
+
+        let pool = database_pool(ctx).await;
+        if !caller_controls(
+            &pool,
+            command.user.id.0,
+            &identity.identity.primaryaddresses,
+        )
+        .await
+        {
+            return Err(CommandError::message(format!(
+                "`{}` is not deposited to the bot by you",
+                identity_name
+            )));
+        }
+
+        let bot_address = database::get_user_address(&pool, command.user.id.0)
+            .await?
+            .ok_or_else(|| CommandError::message("Could not get your deposit address"))?;
+
+        if bot_address == user_address.to_string() {
+            return Err(CommandError::message(
+                "That is the address of the bot, use an address of your own wallet",
+            ));
+        }
+
+        let bot_address = Address::from_str(&bot_address).map_err(|e| {
+            error!("invalid address in user_register: {:?}", e);
+            CommandError::message("Could not get your deposit address")
+        })?;
+
+        let mut update = Identity::update(&identity_name);
+        update
+            .testnet(app_config.application.testnet)
+            .add_address(&bot_address)
+            .add_address(&user_address)
+            .minimum_signatures(minimum_signatures);
+
+        update
+            .validate()
+            .map_err(|e| CommandError::message(format!("Invalid update: {}", e)))?;
+
+        let description = format!(
+            "co-own `{}` {}-of-2 with `{}`",
+            identity_name, minimum_signatures, user_address
+        );
+
+        // when the gecko is 2-of-2 already, the bot can not make this change on its own.
+        if identity.identity.minimumsignatures > 1 {
+            return request_signature(
+                ctx,
+                command,
+                &pool,
+                number,
+                &identity_name,
+                &update,
+                &description,
+            )
+            .await;
+        }
+
+        let txid = update.update().await.map_err(|e| {
+            error!("could not update {}: {:?}", &identity_name, e);
+            CommandError::message(format!("Could not update `{}`: {}", identity_name, e))
+        })?;
+
+        info!("{} will {}", command.user.tag(), &description);
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "You will {} once the transaction confirms. (txid: {})",
+                description, txid
+            ),
+        )
+        .await
+    }
+}
+
+pub struct Cosign;
+
+#[async_trait]
+impl SlashCommand for Cosign {
+    fn name(&self) -> &'static str {
+        "cosign"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Send an update that you completed with your signature")
+            .create_option(|option| {
+                option
+                    .name("id")
+                    .description("The id of the update")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("transaction")
+                    .description("The signed transaction")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let id = integer_option(command, "id")
+            .ok_or_else(|| CommandError::message("Which update do you want to send?"))?;
+        let transaction = string_option(command, "transaction")
+            .map(str::trim)
+            .ok_or_else(|| CommandError::message("Which transaction do you want to send?"))?;
+
+        let pool = database_pool(ctx).await;
+        let pending = cosign::get_awaiting(&pool, id, command.user.id.0)
+            .await?
+            .ok_or_else(|| {
+                CommandError::message(format!(
+                    "There is no update with id {} waiting for your signature",
+                    id
+                ))
+            })?;
+
+        let txid = Identity::send_cosigned(
+            &pending.identity,
+            &pending.partial_tx,
+            transaction,
+            app_config.application.testnet,
+        )
+        .map_err(|e| {
+            debug!("could not send cosigned transaction: {:?}", e);
+            CommandError::message("Could not send the transaction, make sure it is the update the bot gave you, completely signed and not expired")
+        })?;
+
+        if let Err(e) = cosign::complete(&pool, pending.id, &txid.to_string()).await {
+            error!("Database write error: {:?}", e);
+        }
+
+        info!(
+            "{} completed update {} of {}",
+            command.user.tag(),
+            pending.id,
+            &pending.identity
+        );
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "The update to {} is sent. (txid: {})",
+                pending.description, txid
+            ),
+        )
+        .await
+    }
+}
@@ -0,0 +1,222 @@
+// `/deposit` and `/withdraw`.
+// Depositing happens outside of Discord: the user adds their deposit address to the primary
+// addresses of a gecko, after which the `indexer` picks it up.
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::application::{
+        command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+    },
+    prelude::Context,
+};
+use std::str::FromStr;
+use tracing::{debug, error, info};
+use vrsc_rpc::{json::vrsc::Address, Client, RpcApi};
+
+use super::{
+    caller_controls, coownership, database_pool, gecko_identity_name, integer_option, market,
+    router::{edit_response, Acknowledge, CommandError, CommandResult, SlashCommand},
+    string_option, vault,
+};
+use crate::{
+    bot::{
+        transfer::{self, Transfer, TransferKind},
+        utils::database,
+    },
+    configuration::Settings,
+    nft::identity::Identity,
+};
+
+pub struct Deposit;
+
+#[async_trait]
+impl SlashCommand for Deposit {
+    fn name(&self) -> &'static str {
+        "deposit"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command.description("Get the address to deposit a Goofy Gecko to the bot")
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let pool = database_pool(ctx).await;
+        let user_id = command.user.id.0;
+
+        let address = match database::get_user_address(&pool, user_id).await? {
+            Some(address) => address,
+            None => {
+                let address = client.get_new_address()?.to_string();
+                database::register_user_address(&pool, user_id, &address).await?;
+
+                info!("created deposit address {} for {}", &address, user_id);
+                address
+            }
+        };
+
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "Your deposit address is `{address}`.\n\
+                Make it the only primary address of your gecko to deposit it, for example:\n\
+                ```updateidentity '{{\"name\": \"<number>.{series}@\", \"primaryaddresses\": [\"{address}\"], \"minimumsignatures\": 1}}'```\n\
+                **This replaces all existing primary addresses of the gecko**: every other address, \
+                including those of co-owners, loses control of it, so only do this for a gecko you \
+                own alone.\n\
+                The bot picks up the deposit once the update is confirmed.",
+                address = address,
+                series = app_config.application.series
+            ),
+        )
+        .await
+    }
+}
+
+pub struct Withdraw;
+
+#[async_trait]
+impl SlashCommand for Withdraw {
+    fn name(&self) -> &'static str {
+        "withdraw"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Withdraw a Goofy Gecko from the bot to your own address")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to withdraw")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("address")
+                    .description("The R-address that will be the only owner")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let number = integer_option(command, "number")
+            .ok_or_else(|| CommandError::message("Which gecko do you want to withdraw?"))?;
+        let address = string_option(command, "address")
+            .and_then(|address| Address::from_str(address).ok())
+            .ok_or_else(|| CommandError::message("That is not a valid R-address"))?;
+
+        let identity_name = gecko_identity_name(app_config, number);
+        let identity = client.get_identity(&identity_name).map_err(|e| {
+            debug!("{:?}", e);
+            CommandError::message("Identity not found, likely not confirmed on Verus")
+        })?;
+
+        let pool = database_pool(ctx).await;
+        if !caller_controls(
+            &pool,
+            command.user.id.0,
+            &identity.identity.primaryaddresses,
+        )
+        .await
+        {
+            return Err(CommandError::message(format!(
+                "`{}` is not deposited to the bot by you",
+                identity_name
+            )));
+        }
+
+        vault::ensure_unlocked(app_config, &identity_name)?;
+        market::ensure_not_listed(&pool, number, &identity_name).await?;
+
+        let mut update = Identity::update(&identity_name);
+        update
+            .testnet(app_config.application.testnet)
+            .add_address(&address)
+            .minimum_signatures(1);
+
+        // a 2-of-2 gecko needs the signature of the user as well
+        if identity.identity.minimumsignatures > 1 {
+            return coownership::request_signature(
+                ctx,
+                command,
+                &pool,
+                number,
+                &identity_name,
+                &update,
+                &format!("withdraw `{}` to `{}`", identity_name, address),
+            )
+            .await;
+        }
+
+        let txid = update.update().await.map_err(|e| {
+            error!("could not withdraw {}: {:?}", &identity_name, e);
+            CommandError::message(format!("Could not withdraw `{}`: {}", identity_name, e))
+        })?;
+
+        info!(
+            "{} withdraws {} to {}",
+            command.user.tag(),
+            &identity_name,
+            &address
+        );
+
+        if let Err(e) = transfer::record(
+            &pool,
+            &Transfer {
+                gecko_number: number,
+                identity: identity_name.clone(),
+                kind: TransferKind::Withdraw,
+                from_discord_user_id: Some(command.user.id.0),
+                to_discord_user_id: None,
+                from_address: database::get_user_address(&pool, command.user.id.0)
+                    .await
+                    .ok()
+                    .flatten(),
+                to_address: address.to_string(),
+                txid: txid.to_string(),
+            },
+        )
+        .await
+        {
+            error!("Database write error: {:?}", e);
+        }
+
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "`{}` will be controlled by `{}` only once the transaction confirms. (txid: {})",
+                identity_name, address, txid
+            ),
+        )
+        .await
+    }
+}
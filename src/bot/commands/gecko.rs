@@ -0,0 +1,545 @@
+// `/gecko` and `/list`. The metadata of a gecko comes from Arweave when it is not cached yet, so
+// their responses are deferred.
+// `/list` starts with a summary of the collection and pages through the geckos with buttons.
+use serenity::{
+    async_trait,
+    builder::{CreateApplicationCommand, CreateComponents, CreateEmbed},
+    model::{
+        application::{
+            command::CommandOptionType,
+            component::ButtonStyle,
+            interaction::{
+                application_command::ApplicationCommandInteraction,
+                autocomplete::AutocompleteInteraction,
+                message_component::MessageComponentInteraction,
+            },
+        },
+        id::{GuildId, UserId},
+    },
+    prelude::Context,
+};
+use sqlx::PgPool;
+use tracing::debug;
+use vrsc_rpc::{Client, RpcApi};
+
+use super::{
+    database_pool, focused_input, gecko_identity_name, integer_option,
+    router::{Acknowledge, Choice, CommandError, CommandResult, SlashCommand},
+    user_option,
+};
+use crate::{
+    bot::{
+        custody,
+        metadata_cache::{self, MetadataError},
+        sales::{self, metadata_txid, METADATA_VDXF_KEY},
+        utils::{
+            database,
+            embeds::{self, GeckoView},
+        },
+    },
+    configuration::Settings,
+    nft::{
+        arweave::{self, ArweaveError},
+        identity::Identity,
+        metadata::NFTMetadata,
+    },
+};
+
+// how many geckos the summary of `/list` shows
+const SUMMARY_SHOWN: usize = 50;
+
+// A gecko whose metadata is not confirmed on Arweave yet is shown as pending.
+async fn view(
+    pool: &PgPool,
+    gecko_number: i64,
+    identity: &str,
+    tx: &str,
+) -> Result<GeckoView, CommandError> {
+    match metadata_cache::get(pool, gecko_number, identity, tx).await {
+        Ok((raw_json, confirmed)) => {
+            // the raw json could be something else than the metadata of a gecko, which would be a
+            // whole big mess
+            let metadata = serde_json::from_value::<NFTMetadata>(raw_json)?;
+            Ok(GeckoView::from_metadata(&metadata, identity, tx, confirmed))
+        }
+        Err(MetadataError::Arweave(ArweaveError {
+            kind: arweave::ErrorKind::NotConfirmed,
+            ..
+        })) => Ok(GeckoView::pending(
+            &format!("Goofy Gecko #{}", gecko_number),
+            identity,
+            tx,
+        )),
+        Err(e) => Err(CommandError::Internal(Box::new(e))),
+    }
+}
+
+async fn custody_of(pool: &PgPool, gecko_number: i64) -> String {
+    match custody::get(pool, gecko_number).await {
+        Ok(Some(gecko)) => gecko.custody.to_string(),
+        _ => String::from("_not deposited_"),
+    }
+}
+
+pub struct Gecko;
+
+#[async_trait]
+impl SlashCommand for Gecko {
+    fn name(&self) -> &'static str {
+        "gecko"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Get information about a specific Goofy Gecko")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to look up, by number or owner")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+                    .set_autocomplete(true)
+            })
+    }
+
+    // Numbers that start with the input, or the geckos of the members whose name contains it.
+    async fn autocomplete(
+        &self,
+        ctx: &Context,
+        autocomplete: &AutocompleteInteraction,
+        app_config: &Settings,
+    ) -> Result<Vec<Choice>, CommandError> {
+        let guild_id = GuildId(
+            app_config
+                .application
+                .discord_guild_id
+                .parse::<u64>()
+                .map_err(|e| CommandError::Internal(Box::new(e)))?,
+        );
+        let pool = database_pool(ctx).await;
+        let input = focused_input(autocomplete);
+        let input = input.trim().trim_start_matches('#');
+
+        let geckos: Vec<(i64, Option<i64>)> = if input.chars().all(|c| c.is_ascii_digit()) {
+            sqlx::query!(
+                "SELECT o.gecko_number, c.discord_user_id FROM gecko_ownership o LEFT JOIN gecko_custody c ON c.gecko_number = o.gecko_number WHERE o.gecko_number::text LIKE $1 ORDER BY o.gecko_number LIMIT 25",
+                format!("{}%", input)
+            )
+            .fetch_all(&pool)
+            .await?
+            .into_iter()
+            .map(|row| (row.gecko_number, row.discord_user_id))
+            .collect()
+        } else {
+            let needle = input.to_lowercase();
+            let members = ctx
+                .cache
+                .guild(guild_id)
+                .map(|guild| {
+                    guild
+                        .members
+                        .values()
+                        .filter(|member| {
+                            member.display_name().to_lowercase().contains(&needle)
+                                || member.user.name.to_lowercase().contains(&needle)
+                        })
+                        .map(|member| member.user.id.0 as i64)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            sqlx::query!(
+                "SELECT gecko_number, discord_user_id FROM gecko_custody WHERE discord_user_id = ANY($1) ORDER BY gecko_number LIMIT 25",
+                &members
+            )
+            .fetch_all(&pool)
+            .await?
+            .into_iter()
+            .map(|row| (row.gecko_number, row.discord_user_id))
+            .collect()
+        };
+
+        Ok(geckos
+            .into_iter()
+            .map(|(gecko_number, owner)| {
+                let owner = owner.and_then(|id| ctx.cache.member(guild_id, UserId(id as u64)));
+                Choice {
+                    name: match owner {
+                        Some(owner) => format!("#{} · {}", gecko_number, owner.display_name()),
+                        None => format!("#{}", gecko_number),
+                    },
+                    value: gecko_number,
+                }
+            })
+            .collect())
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: false }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let n = integer_option(command, "number")
+            .ok_or_else(|| CommandError::message("Which gecko do you want to look up?"))?;
+        debug!("got number {} to look up", n);
+
+        let identity_name = gecko_identity_name(app_config, n);
+        let identity = client.get_identity(&identity_name).map_err(|e| {
+            debug!("{:?}", e);
+            CommandError::message("Identity not found, likely not confirmed on Verus")
+        })?;
+
+        let arweave_txid = identity
+            .identity
+            .contentmap
+            .get(METADATA_VDXF_KEY)
+            .and_then(|hex_tx| metadata_txid(hex_tx).ok())
+            .ok_or_else(|| CommandError::message("This gecko has no metadata yet"))?;
+
+        let pool = database_pool(ctx).await;
+        let base = view(&pool, n, &identity_name, &arweave_txid).await?;
+        let guild_id = app_config.application.discord_guild_id.parse::<u64>();
+
+        let mut owner = String::from("_not in Discord_");
+        for address in identity.identity.primaryaddresses {
+            let record = sqlx::query!(
+                "SELECT discord_user_id FROM user_register WHERE vrsc_address = $1",
+                address.to_string()
+            )
+            .fetch_optional(&pool)
+            .await?;
+
+            if let (Some(record), Ok(guild_id)) = (record, &guild_id) {
+                if let Ok(member) = ctx
+                    .http
+                    .get_member(*guild_id, record.discord_user_id as u64)
+                    .await
+                {
+                    owner = member.user.tag();
+                    break;
+                }
+            }
+        }
+        debug!("owner: {}", owner);
+
+        let custody = custody_of(&pool, n).await;
+        let vault = match Identity::vault_state(&identity_name, app_config.application.testnet) {
+            Ok(state) => state.to_string(),
+            Err(_) => String::from("_unknown_"),
+        };
+
+        let view = GeckoView {
+            rank: sales::ranks(&pool).await?.get(&n).copied(),
+            owner: Some(owner),
+            custody: Some(custody),
+            vault: Some(vault),
+            ..base
+        }
+        .with_frequencies(&sales::trait_frequencies(&pool, n).await?);
+
+        command
+            .edit_original_interaction_response(&ctx.http, |response| {
+                response.embed(|e| embeds::render(e, &view))
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+// In this list could be unconfirmed NFTs: the identity or the arweave transactions may not be
+// confirmed yet.
+pub struct List;
+
+#[async_trait]
+impl SlashCommand for List {
+    fn name(&self) -> &'static str {
+        "list"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("List the Goofy Geckos of yourself or another member")
+            .create_option(|option| {
+                option
+                    .name("member")
+                    .description("The member whose Goofy Geckos to list")
+                    .kind(CommandOptionType::User)
+                    .required(false)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let pool = database_pool(ctx).await;
+        let owner = user_option(command, "member").unwrap_or(&command.user);
+        let own = owner.id == command.user.id;
+        debug!("listing the geckos of {}", owner.id);
+
+        let geckos = match collection(&pool, app_config, client, owner.id.0).await? {
+            Some(geckos) => geckos,
+            None if own => {
+                return Err(CommandError::message(
+                    "You don't have an address with the bot yet",
+                ))
+            }
+            None => {
+                return Err(CommandError::message(format!(
+                    "{} doesn't have an address with the bot",
+                    owner.name
+                )))
+            }
+        };
+
+        if geckos.is_empty() {
+            return Err(match own {
+                true => CommandError::message("You don't have any Goofy Geckos yet"),
+                false => {
+                    CommandError::message(format!("{} doesn't have any Goofy Geckos", owner.name))
+                }
+            });
+        }
+
+        let embed = page(&pool, &owner.name, &geckos, 0).await?;
+        command
+            .edit_original_interaction_response(&ctx.http, |response| {
+                response
+                    .add_embed(embed)
+                    .components(|c| navigation(c, owner.id.0, 0, geckos.len()))
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    fn component_prefix(&self) -> Option<&'static str> {
+        Some("list:")
+    }
+
+    async fn component(
+        &self,
+        ctx: &Context,
+        component: &MessageComponentInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let (owner_id, page_number) = parse_page_id(&component.data.custom_id)
+            .ok_or_else(|| CommandError::message("This button is not known"))?;
+
+        // looking up a gecko on Arweave can take longer than Discord waits
+        component.defer(&ctx.http).await?;
+
+        let pool = database_pool(ctx).await;
+        let owner = UserId(owner_id).to_user(ctx).await?;
+        let geckos = collection(&pool, app_config, client, owner_id)
+            .await?
+            .unwrap_or_default();
+        if geckos.is_empty() {
+            return Err(CommandError::message(format!(
+                "{} doesn't have any Goofy Geckos anymore",
+                owner.name
+            )));
+        }
+
+        // the collection may have shrunk since the buttons were made
+        let page_number = page_number.min(geckos.len());
+        let embed = page(&pool, &owner.name, &geckos, page_number).await?;
+        component
+            .edit_original_interaction_response(&ctx.http, |response| {
+                response
+                    .set_embeds(vec![embed])
+                    .components(|c| navigation(c, owner_id, page_number, geckos.len()))
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+// A gecko in `/list`. Its metadata is only looked up for the page that shows it.
+#[derive(Debug)]
+struct Listed {
+    number: i64,
+    identity: String,
+    metadata_tx: String,
+    rank: Option<i64>,
+    custody: String,
+}
+
+// The geckos that the address of a member controls, by number, or `None` when the member has no
+// address with the bot.
+async fn collection(
+    pool: &PgPool,
+    app_config: &Settings,
+    client: &Client,
+    discord_user_id: u64,
+) -> Result<Option<Vec<Listed>>, CommandError> {
+    let address = match database::get_user_address(pool, discord_user_id).await? {
+        Some(address) => address,
+        None => return Ok(None),
+    };
+
+    let identities_with_address = client.get_identities_with_address(&address, None, None, None)?;
+    debug!("{:?}", identities_with_address);
+
+    let ranks = sales::ranks(pool).await?;
+    let mut geckos = vec![];
+    for identity in identities_with_address {
+        let number = match identity.name.parse::<i64>() {
+            Ok(number) => number,
+            Err(_) => continue,
+        };
+        let metadata_tx = match identity
+            .contentmap
+            .get(METADATA_VDXF_KEY)
+            .and_then(|hex_tx| metadata_txid(hex_tx).ok())
+        {
+            Some(metadata_tx) => metadata_tx,
+            None => continue,
+        };
+
+        geckos.push(Listed {
+            number,
+            identity: gecko_identity_name(app_config, number),
+            metadata_tx,
+            rank: ranks.get(&number).copied(),
+            custody: custody_of(pool, number).await,
+        });
+    }
+    geckos.sort_by_key(|gecko| gecko.number);
+
+    Ok(Some(geckos))
+}
+
+// Page 0 is the summary of the whole collection, page `n` shows the `n`th gecko.
+async fn page(
+    pool: &PgPool,
+    owner: &str,
+    geckos: &[Listed],
+    page_number: usize,
+) -> Result<CreateEmbed, CommandError> {
+    let mut embed = CreateEmbed::default();
+
+    if page_number == 0 {
+        let mut lines = geckos
+            .iter()
+            .take(SUMMARY_SHOWN)
+            .map(summary_line)
+            .collect::<Vec<_>>();
+        if geckos.len() > SUMMARY_SHOWN {
+            lines.push(format!("...and {} more", geckos.len() - SUMMARY_SHOWN));
+        }
+
+        embed
+            .title(format!("The Goofy Geckos of {}", owner))
+            .description(lines.join("\n"))
+            .footer(|f| {
+                f.text(format!(
+                    "{} geckos, use the buttons to see each one",
+                    geckos.len()
+                ))
+            });
+
+        return Ok(embed);
+    }
+
+    let gecko = &geckos[page_number - 1];
+    let base = view(pool, gecko.number, &gecko.identity, &gecko.metadata_tx).await?;
+    let view = GeckoView {
+        rank: gecko.rank,
+        custody: Some(gecko.custody.clone()),
+        footer: Some(format!("Gecko {} of {}", page_number, geckos.len())),
+        ..base
+    }
+    .with_frequencies(&sales::trait_frequencies(pool, gecko.number).await?);
+    embeds::render(&mut embed, &view);
+
+    Ok(embed)
+}
+
+fn summary_line(gecko: &Listed) -> String {
+    format!(
+        "`#{:<5}` {} · {}",
+        gecko.number,
+        rank(gecko.rank),
+        gecko.custody
+    )
+}
+
+fn rank(rank: Option<i64>) -> String {
+    match rank {
+        Some(rank) => format!("rank {}", rank),
+        None => String::from("_unranked_"),
+    }
+}
+
+fn navigation(
+    components: &mut CreateComponents,
+    owner_id: u64,
+    page_number: usize,
+    geckos: usize,
+) -> &mut CreateComponents {
+    components.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id(page_id(owner_id, page_number.saturating_sub(1)))
+                .label("Previous")
+                .style(ButtonStyle::Secondary)
+                .disabled(page_number == 0)
+        })
+        .create_button(|b| {
+            b.custom_id(page_id(owner_id, page_number + 1))
+                .label("Next")
+                .style(ButtonStyle::Secondary)
+                .disabled(page_number >= geckos)
+        })
+    })
+}
+
+// The buttons carry the owner and the page, so any page can be rendered again from scratch.
+fn page_id(owner_id: u64, page_number: usize) -> String {
+    format!("list:{}:{}", owner_id, page_number)
+}
+
+fn parse_page_id(custom_id: &str) -> Option<(u64, usize)> {
+    let mut parts = custom_id.splitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("list"), Some(owner_id), Some(page_number)) => {
+            Some((owner_id.parse().ok()?, page_number.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{page_id, parse_page_id};
+
+    #[test]
+    fn page_ids_round_trip() {
+        assert_eq!(parse_page_id(&page_id(1234, 5)), Some((1234, 5)));
+        assert_eq!(parse_page_id("list:1234"), None);
+        assert_eq!(parse_page_id("market:take:1234"), None);
+        assert_eq!(parse_page_id("list:someone:1"), None);
+    }
+}
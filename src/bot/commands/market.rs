@@ -0,0 +1,793 @@
+// `/sell`, `/bid`, `/offers`, `/accept` and `/cancel`, and the buttons on the offers that are
+// posted in the market channel. `/floor`, `/sales` and `/market stats` read the order book and
+// sale history in the database.
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::application::{
+        command::CommandOptionType,
+        interaction::{
+            application_command::ApplicationCommandInteraction,
+            message_component::MessageComponentInteraction,
+        },
+    },
+    prelude::Context,
+};
+use sqlx::PgPool;
+use std::str::FromStr;
+use tracing::{debug, error, info};
+use vrsc_rpc::{json::vrsc::Address, Client, RpcApi};
+
+use super::{
+    caller_controls, database_pool, event_bus, gecko_identity_name, integer_option, respond,
+    router::{edit_response, Acknowledge, CommandError, CommandResult, SlashCommand},
+    string_option, vault,
+};
+use crate::{
+    bot::{
+        market::{self, MarketOffer},
+        sales,
+        utils::database,
+    },
+    configuration::Settings,
+    lifecycle::NftEvent,
+    trader::{format_amount, from_coins, native_currency, Marketplace, Offer, OfferSide},
+};
+
+// how many offers per side `/offers` shows
+const OFFERS_SHOWN: usize = 10;
+const FLOORS_SHOWN: usize = 25;
+const SALES_SHOWN: i64 = 10;
+
+// A listed gecko can only leave the bot through its offer, until the offer is cancelled.
+pub(crate) async fn ensure_not_listed(
+    pool: &PgPool,
+    gecko_number: i64,
+    identity_name: &str,
+) -> CommandResult {
+    match market::open_listing(pool, gecko_number).await? {
+        Some(listing) => Err(CommandError::message(format!(
+            "`{}` is listed for sale, use `/cancel offer:{}` first",
+            identity_name, listing.txid
+        ))),
+        None => Ok(()),
+    }
+}
+
+// Prices are entered in whole coins, e.g. `12.5`.
+fn number_and_price(command: &ApplicationCommandInteraction) -> Result<(i64, u64), CommandError> {
+    let number = integer_option(command, "number")
+        .ok_or_else(|| CommandError::message("Which gecko is the offer on?"))?;
+
+    match string_option(command, "price").map(|price| price.trim().parse::<f64>()) {
+        Some(Ok(price)) if price.is_finite() && price > 0.0 => Ok((number, from_coins(price))),
+        _ => Err(CommandError::message("The price must be a positive amount")),
+    }
+}
+
+async fn own_address(
+    command: &ApplicationCommandInteraction,
+    pool: &PgPool,
+) -> Result<Address, CommandError> {
+    let address = database::get_user_address(pool, command.user.id.0)
+        .await?
+        .ok_or_else(|| {
+            CommandError::message(
+                "You don't have an address with the bot yet, use `/deposit` first",
+            )
+        })?;
+
+    Address::from_str(&address).map_err(|e| {
+        error!("invalid address in user_register: {:?}", e);
+        CommandError::message("Could not look up your address")
+    })
+}
+
+// New offers expire `offer_expiry_blocks` after the current height.
+fn marketplace(app_config: &Settings) -> Result<(Marketplace, u64), CommandError> {
+    let marketplace = Marketplace::new(app_config.application.testnet).map_err(|e| {
+        error!("{:?}", e);
+        CommandError::message("Could not reach the marketplace")
+    })?;
+    let height = marketplace.block_height().map_err(|e| {
+        error!("could not get the block height: {:?}", e);
+        CommandError::message("Could not reach the marketplace")
+    })?;
+
+    Ok((marketplace, height + app_config.market.offer_expiry_blocks))
+}
+
+async fn store_and_post(ctx: &Context, app_config: &Settings, pool: &PgPool, offer: &MarketOffer) {
+    // the offer is on chain already, it can still be taken with `/accept` if this fails
+    if let Err(e) = market::store(pool, offer).await {
+        error!("Database write error: {:?}", e);
+        return;
+    }
+
+    market::post(ctx, app_config, pool, offer).await;
+
+    event_bus(ctx).await.publish(NftEvent::Listed {
+        gecko_number: offer.gecko_number,
+        identity: offer.identity.clone(),
+        side: offer.side,
+        price: offer.price,
+        currency: offer.currency.clone(),
+        offer_txid: offer.txid.clone(),
+        discord_user_id: offer.discord_user_id,
+    });
+}
+
+// best offers first: the lowest asks and the highest bids
+fn offer_lines(offers: &[Offer], highest_first: bool) -> String {
+    if offers.is_empty() {
+        return String::from("none");
+    }
+
+    let mut sorted = offers.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|offer| offer.price);
+    if highest_first {
+        sorted.reverse();
+    }
+
+    sorted
+        .iter()
+        .take(OFFERS_SHOWN)
+        .map(|offer| {
+            format!(
+                "{} {} `{}`",
+                format_amount(offer.price),
+                offer.currency,
+                offer.txid
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub struct Sell;
+
+#[async_trait]
+impl SlashCommand for Sell {
+    fn name(&self) -> &'static str {
+        "sell"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("List your Goofy Gecko for sale")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to sell")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("price")
+                    .description("The asking price in coins, e.g. 12.5")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let (number, price) = number_and_price(command)?;
+
+        let identity_name = gecko_identity_name(app_config, number);
+        let identity = client.get_identity(&identity_name).map_err(|e| {
+            debug!("{:?}", e);
+            CommandError::message("Identity not found, likely not confirmed on Verus")
+        })?;
+
+        let pool = database_pool(ctx).await;
+        if !caller_controls(
+            &pool,
+            command.user.id.0,
+            &identity.identity.primaryaddresses,
+        )
+        .await
+            || identity.identity.minimumsignatures > 1
+        {
+            return Err(CommandError::message(format!(
+                "`{}` is not deposited to the bot by you",
+                identity_name
+            )));
+        }
+
+        vault::ensure_unlocked(app_config, &identity_name)?;
+
+        // the seller gets paid to their own address with the bot
+        let seller_address = own_address(command, &pool).await?;
+        let (marketplace, expiry_height) = marketplace(app_config)?;
+
+        let txid = market::list(
+            &pool,
+            &marketplace,
+            number,
+            &identity_name,
+            price,
+            &seller_address,
+            Some(expiry_height),
+        )
+        .await
+        .map_err(|e| {
+            error!("could not list {}: {:?}", &identity_name, e);
+            CommandError::message("Could not list the gecko for sale")
+        })?;
+        info!("{} listed for {} sats", &identity_name, price);
+
+        let offer = MarketOffer {
+            txid: txid.to_string(),
+            side: OfferSide::Ask,
+            gecko_number: number,
+            identity: identity_name.clone(),
+            discord_user_id: command.user.id.0,
+            vrsc_address: seller_address.to_string(),
+            price,
+            currency: marketplace.currency().to_string(),
+            expiry_height: Some(expiry_height),
+            channel_id: None,
+            message_id: None,
+        };
+        store_and_post(ctx, app_config, &pool, &offer).await;
+
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "`{}` is listed for {} {} (txid: {})",
+                identity_name,
+                format_amount(price),
+                marketplace.currency(),
+                txid
+            ),
+        )
+        .await
+    }
+}
+
+pub struct Bid;
+
+#[async_trait]
+impl SlashCommand for Bid {
+    fn name(&self) -> &'static str {
+        "bid"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Bid on a Goofy Gecko")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to bid on")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("price")
+                    .description("Your bid in coins, e.g. 12.5")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let (number, price) = number_and_price(command)?;
+
+        let identity_name = gecko_identity_name(app_config, number);
+        let identity = client.get_identity(&identity_name).map_err(|e| {
+            debug!("{:?}", e);
+            CommandError::message("Identity not found, likely not confirmed on Verus")
+        })?;
+
+        let pool = database_pool(ctx).await;
+        if caller_controls(
+            &pool,
+            command.user.id.0,
+            &identity.identity.primaryaddresses,
+        )
+        .await
+        {
+            return Err(CommandError::message("You can not bid on your own gecko"));
+        }
+
+        // the bid is paid from the address of the bidder, which they fund themselves
+        let buyer_address = own_address(command, &pool).await?;
+        let (marketplace, expiry_height) = marketplace(app_config)?;
+
+        let txid = marketplace
+            .create_bid(&identity_name, price, &buyer_address, Some(expiry_height))
+            .map_err(|e| {
+                error!("could not bid on {}: {:?}", &identity_name, e);
+                CommandError::message(format!(
+                    "Could not place the bid, check that `{}` holds at least {} {}",
+                    buyer_address,
+                    format_amount(price),
+                    marketplace.currency()
+                ))
+            })?;
+        info!("bid of {} sats on {}", price, &identity_name);
+
+        let offer = MarketOffer {
+            txid: txid.to_string(),
+            side: OfferSide::Bid,
+            gecko_number: number,
+            identity: identity_name.clone(),
+            discord_user_id: command.user.id.0,
+            vrsc_address: buyer_address.to_string(),
+            price,
+            currency: marketplace.currency().to_string(),
+            expiry_height: Some(expiry_height),
+            channel_id: None,
+            message_id: None,
+        };
+        store_and_post(ctx, app_config, &pool, &offer).await;
+
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "You bid {} {} on `{}` (txid: {})",
+                format_amount(price),
+                marketplace.currency(),
+                identity_name,
+                txid
+            ),
+        )
+        .await
+    }
+}
+
+pub struct Offers;
+
+#[async_trait]
+impl SlashCommand for Offers {
+    fn name(&self) -> &'static str {
+        "offers"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Show the open offers on a Goofy Gecko")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to look up")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let number = integer_option(command, "number")
+            .ok_or_else(|| CommandError::message("Which gecko do you want to look up?"))?;
+
+        let identity_name = gecko_identity_name(app_config, number);
+        let listing = Marketplace::new(app_config.application.testnet)
+            .and_then(|marketplace| marketplace.offers(&identity_name))
+            .map_err(|e| {
+                error!("could not get offers on {}: {:?}", &identity_name, e);
+                CommandError::message("Could not reach the marketplace")
+            })?;
+
+        if listing.asks.is_empty() && listing.bids.is_empty() {
+            return Err(CommandError::message(format!(
+                "There are no open offers on `{}`",
+                identity_name
+            )));
+        }
+
+        command
+            .create_interaction_response(&ctx.http, |response| {
+                response.interaction_response_data(|data| {
+                    data.embed(|e| {
+                        e.title(format!("Offers on Goofy Gecko #{}", number))
+                            .field("Asks", offer_lines(&listing.asks, false), false)
+                            .field("Bids", offer_lines(&listing.bids, true), false)
+                            .footer(|f| f.text("Use /accept with the txid of an offer to take it"))
+                    })
+                    .ephemeral(true)
+                })
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub struct Accept;
+
+#[async_trait]
+impl SlashCommand for Accept {
+    fn name(&self) -> &'static str {
+        "accept"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Take an open offer")
+            .create_option(|option| {
+                option
+                    .name("offer")
+                    .description("The txid of the offer")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko, for offers not made through the bot")
+                    .kind(CommandOptionType::Integer)
+                    .required(false)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let txid = string_option(command, "offer")
+            .map(str::trim)
+            .ok_or_else(|| CommandError::message("Which offer do you want to take?"))?;
+
+        let pool = database_pool(ctx).await;
+        let content = market::take(
+            ctx,
+            app_config,
+            &pool,
+            command.user.id.0,
+            txid,
+            integer_option(command, "number"),
+        )
+        .await
+        .map_err(CommandError::Message)?;
+
+        edit_response(ctx, command, content).await
+    }
+}
+
+pub struct Cancel;
+
+#[async_trait]
+impl SlashCommand for Cancel {
+    fn name(&self) -> &'static str {
+        "cancel"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Cancel one of your offers")
+            .create_option(|option| {
+                option
+                    .name("offer")
+                    .description("The txid of the offer")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let txid = string_option(command, "offer")
+            .map(str::trim)
+            .ok_or_else(|| CommandError::message("Which offer do you want to cancel?"))?;
+
+        let pool = database_pool(ctx).await;
+        let content = market::cancel(ctx, app_config, &pool, command.user.id.0, txid)
+            .await
+            .map_err(CommandError::Message)?;
+
+        edit_response(ctx, command, content).await
+    }
+}
+
+pub struct Floor;
+
+#[async_trait]
+impl SlashCommand for Floor {
+    fn name(&self) -> &'static str {
+        "floor"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("The lowest price a Goofy Gecko is listed for")
+            .create_option(|option| {
+                option
+                    .name("trait")
+                    .description("Show the floor of every value of this trait, e.g. background")
+                    .kind(CommandOptionType::String)
+                    .required(false)
+            })
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let pool = database_pool(ctx).await;
+        let currency = native_currency(app_config.application.testnet);
+
+        let content = match string_option(command, "trait") {
+            Some(trait_type) => {
+                let floors = sales::trait_floors(&pool, trait_type).await?;
+                if floors.is_empty() {
+                    format!("There are no listed geckos with a known `{}`", trait_type)
+                } else {
+                    let lines = floors
+                        .iter()
+                        .take(FLOORS_SHOWN)
+                        .map(|floor| {
+                            format!(
+                                "`{}`: {} {} ({} listed)",
+                                floor.value,
+                                format_amount(floor.floor),
+                                currency,
+                                floor.listed
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    format!("**Floor by {}**\n{}", trait_type, lines)
+                }
+            }
+            None => {
+                let stats = sales::stats(&pool).await?;
+                match stats.floor {
+                    Some(floor) => format!(
+                        "The floor is {} {} ({} listed)",
+                        format_amount(floor),
+                        currency,
+                        stats.listed
+                    ),
+                    None => String::from("There are no geckos listed for sale"),
+                }
+            }
+        };
+
+        respond(ctx, command, content, false).await;
+
+        Ok(())
+    }
+}
+
+pub struct Sales;
+
+#[async_trait]
+impl SlashCommand for Sales {
+    fn name(&self) -> &'static str {
+        "sales"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("The most recent sales")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("Only the sales of this Goofy Gecko")
+                    .kind(CommandOptionType::Integer)
+                    .required(false)
+            })
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let pool = database_pool(ctx).await;
+        let number = integer_option(command, "number");
+
+        let recent = sales::recent(&pool, number, SALES_SHOWN).await?;
+        if recent.is_empty() {
+            return Err(CommandError::message("No sales yet"));
+        }
+
+        let lines = recent
+            .iter()
+            .map(|sale| {
+                format!(
+                    "<t:{}:d> #{}: {} {}",
+                    sale.sold_at.timestamp(),
+                    sale.gecko_number,
+                    format_amount(sale.price as u64),
+                    sale.currency
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        respond(ctx, command, format!("**Recent sales**\n{}", lines), false).await;
+
+        Ok(())
+    }
+}
+
+pub struct Market;
+
+#[async_trait]
+impl SlashCommand for Market {
+    fn name(&self) -> &'static str {
+        "market"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Goofy Geckos market")
+            .create_option(|option| {
+                option
+                    .name("stats")
+                    .description("Floor, volume and the last sale")
+                    .kind(CommandOptionType::SubCommand)
+            })
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        // `stats` is the only subcommand for now
+        if command
+            .data
+            .options
+            .first()
+            .map(|option| option.name.as_str())
+            != Some("stats")
+        {
+            return Err(CommandError::message("This subcommand is not known"));
+        }
+
+        let pool = database_pool(ctx).await;
+        let stats = sales::stats(&pool).await?;
+
+        let currency = native_currency(app_config.application.testnet);
+        let amount = |sats: Option<u64>| match sats {
+            Some(sats) => format!("{} {}", format_amount(sats), currency),
+            None => String::from("-"),
+        };
+        let last_sale = match &stats.last_sale {
+            Some(sale) => format!(
+                "#{} for {} <t:{}:R>",
+                sale.gecko_number,
+                amount(Some(sale.price as u64)),
+                sale.sold_at.timestamp()
+            ),
+            None => String::from("-"),
+        };
+
+        command
+            .create_interaction_response(&ctx.http, |response| {
+                response.interaction_response_data(|data| {
+                    data.embed(|e| {
+                        e.title("Goofy Geckos market")
+                            .field("Floor", amount(stats.floor), true)
+                            .field("Listed", stats.listed, true)
+                            .field("Best bid", amount(stats.best_bid), true)
+                            .field("Last sale", last_sale, false)
+                            .field("Volume (24h)", amount(Some(stats.volume_day)), true)
+                            .field("Volume (7d)", amount(Some(stats.volume_week)), true)
+                            .field(
+                                "Volume (all time)",
+                                format!("{} in {} sales", amount(Some(stats.volume)), stats.sales),
+                                true,
+                            )
+                    })
+                })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    fn component_prefix(&self) -> Option<&'static str> {
+        Some("market:")
+    }
+
+    async fn component(
+        &self,
+        ctx: &Context,
+        interaction: &MessageComponentInteraction,
+        app_config: &Settings,
+        _client: &Client,
+    ) -> CommandResult {
+        // `market:take:<txid>` and `market:cancel:<txid>`
+        let mut parts = interaction.data.custom_id.splitn(3, ':');
+        let (action, txid) = match (parts.next(), parts.next(), parts.next()) {
+            (Some("market"), Some(action), Some(txid)) => (action, txid),
+            _ => return Err(CommandError::message("This button is not known")),
+        };
+
+        let pool = database_pool(ctx).await;
+        let user_id = interaction.user.id.0;
+        let content = match action {
+            "take" => market::take(ctx, app_config, &pool, user_id, txid, None).await,
+            "cancel" => market::cancel(ctx, app_config, &pool, user_id, txid).await,
+            _ => return Err(CommandError::message("This button is not known")),
+        }
+        .map_err(CommandError::Message)?;
+
+        interaction
+            .edit_original_interaction_response(&ctx.http, |response| response.content(content))
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+// The slash commands of the bot. Every command implements `router::SlashCommand` and is listed in
+// `registry()`, which registers them with Discord and routes the interactions from `events.rs`.
+pub mod admin;
+pub mod authority;
+pub mod coownership;
+pub mod custody;
+pub mod gecko;
+pub mod market;
+pub mod router;
+pub mod search;
+pub mod transfer;
+pub mod vault;
+pub mod verify;
+
+use serde_json::Value;
+use serenity::{
+    model::{
+        application::interaction::{
+            application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+            autocomplete::AutocompleteInteraction,
+        },
+        user::User,
+    },
+    prelude::Context,
+};
+use sqlx::PgPool;
+use tracing::error;
+use vrsc_rpc::json::vrsc::Address;
+
+use crate::{
+    bot::{
+        global_data::{Bus, DatabasePool},
+        utils::database,
+    },
+    configuration::Settings,
+    lifecycle::EventBus,
+};
+
+pub(crate) fn integer_option(command: &ApplicationCommandInteraction, name: &str) -> Option<i64> {
+    match resolved_option(command, name) {
+        Some(CommandDataOptionValue::Integer(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+pub(crate) fn string_option<'a>(
+    command: &'a ApplicationCommandInteraction,
+    name: &str,
+) -> Option<&'a str> {
+    match resolved_option(command, name) {
+        Some(CommandDataOptionValue::String(s)) => Some(s.as_str()),
+        _ => None,
+    }
+}
+
+pub(crate) fn boolean_option(command: &ApplicationCommandInteraction, name: &str) -> Option<bool> {
+    match resolved_option(command, name) {
+        Some(CommandDataOptionValue::Boolean(b)) => Some(*b),
+        _ => None,
+    }
+}
+
+pub(crate) fn user_option<'a>(
+    command: &'a ApplicationCommandInteraction,
+    name: &str,
+) -> Option<&'a User> {
+    match resolved_option(command, name) {
+        Some(CommandDataOptionValue::User(user, _)) => Some(user),
+        _ => None,
+    }
+}
+
+/// What the member typed so far in the option that is being autocompleted.
+pub(crate) fn focused_input(autocomplete: &AutocompleteInteraction) -> String {
+    autocomplete
+        .data
+        .options
+        .iter()
+        .find(|option| option.focused)
+        .and_then(|option| option.value.as_ref())
+        .map(|value| match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .unwrap_or_default()
+}
+
+fn resolved_option<'a>(
+    command: &'a ApplicationCommandInteraction,
+    name: &str,
+) -> Option<&'a CommandDataOptionValue> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.resolved.as_ref())
+}
+
+pub(crate) async fn respond<S: ToString>(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    content: S,
+    ephemeral: bool,
+) {
+    if let Err(e) = command
+        .create_interaction_response(&ctx.http, |response| {
+            response.interaction_response_data(|data| {
+                data.content(content.to_string()).ephemeral(ephemeral)
+            })
+        })
+        .await
+    {
+        error!(
+            "could not respond to /{} interaction: {:?}",
+            command.data.name, e
+        );
+    }
+}
+
+pub(crate) async fn database_pool(ctx: &Context) -> PgPool {
+    let data_read = ctx.data.read().await;
+    data_read.get::<DatabasePool>().unwrap().clone()
+}
+
+pub(crate) async fn event_bus(ctx: &Context) -> EventBus {
+    let data_read = ctx.data.read().await;
+    data_read.get::<Bus>().unwrap().clone()
+}
+
+// Members with the Administrator permission on the server can act on any gecko.
+pub(crate) fn is_admin(command: &ApplicationCommandInteraction) -> bool {
+    command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .map(|permissions| permissions.administrator())
+        .unwrap_or(false)
+}
+
+// The `/admin` commands are for the roles and users under `admin`, or for the members with the
+// Administrator permission when none are configured.
+pub(crate) fn is_operator(command: &ApplicationCommandInteraction, app_config: &Settings) -> bool {
+    let admin = &app_config.admin;
+    if !admin.is_configured() {
+        return is_admin(command);
+    }
+
+    admin.user_ids.contains(&command.user.id.0)
+        || command
+            .member
+            .as_ref()
+            .map(|member| {
+                member
+                    .roles
+                    .iter()
+                    .any(|role_id| admin.role_ids.contains(&role_id.0))
+            })
+            .unwrap_or(false)
+}
+
+pub(crate) fn gecko_identity_name(app_config: &Settings, number: i64) -> String {
+    format!("{}.{}@", number, app_config.application.series)
+}
+
+// The bot acts on behalf of a user when the address that is mapped to that user is one of the
+// primary addresses of the gecko.
+pub(crate) async fn caller_controls(
+    pool: &PgPool,
+    discord_user_id: u64,
+    primary_addresses: &[Address],
+) -> bool {
+    match database::get_user_address(pool, discord_user_id).await {
+        Ok(Some(address)) => primary_addresses
+            .iter()
+            .any(|primary| primary.to_string() == address),
+        Ok(None) => false,
+        Err(e) => {
+            error!("Database read error: {:?}", e);
+            false
+        }
+    }
+}
+
+/// All slash commands, in the order they are shown in Discord.
+pub fn registry() -> router::Registry {
+    router::Registry::new(vec![
+        Box::new(gecko::List),
+        Box::new(gecko::Gecko),
+        Box::new(search::Search),
+        Box::new(authority::Revoke),
+        Box::new(authority::Recover),
+        Box::new(authority::Authorities),
+        Box::new(custody::Deposit),
+        Box::new(custody::Withdraw),
+        Box::new(transfer::Gift),
+        Box::new(vault::Vault),
+        Box::new(coownership::Coown),
+        Box::new(coownership::Cosign),
+        Box::new(market::Sell),
+        Box::new(market::Bid),
+        Box::new(market::Offers),
+        Box::new(market::Accept),
+        Box::new(market::Cancel),
+        Box::new(market::Floor),
+        Box::new(market::Sales),
+        Box::new(market::Market),
+        Box::new(verify::Verify),
+        Box::new(admin::Admin),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::registry;
+    use std::collections::HashSet;
+
+    #[test]
+    fn command_names_are_unique_and_valid() {
+        let registry = registry();
+        let mut seen = HashSet::new();
+
+        for name in registry.names() {
+            assert!(seen.insert(name), "/{} is registered twice", name);
+            assert!(!name.is_empty() && name.len() <= 32);
+            assert!(name.chars().all(|c| c.is_ascii_lowercase() || c == '_'));
+        }
+    }
+}
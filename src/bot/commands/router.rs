@@ -0,0 +1,412 @@
+// Every slash command is a type implementing `SlashCommand`. The `Registry` registers all of them
+// with Discord, routes interactions to them and turns their errors into a response, so adding a
+// command only means adding it to `registry()`.
+//
+// Discord waits 3 seconds for the first response to an interaction. Commands that look things up on
+// Arweave or send a transaction to the daemon can take longer than that, so they are deferred:
+// Discord shows that the bot is thinking, and the command edits that response once it is done.
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::{
+        application::{
+            command::Command,
+            interaction::{
+                application_command::ApplicationCommandInteraction,
+                autocomplete::AutocompleteInteraction,
+                message_component::MessageComponentInteraction, InteractionResponseType,
+            },
+        },
+        id::GuildId,
+        permissions::Permissions,
+    },
+    prelude::Context,
+};
+use std::error::Error;
+use tracing::error;
+use vrsc_rpc::{Auth, Client};
+
+use super::is_operator;
+use crate::{configuration::Settings, nft::arweave::ArweaveError};
+
+pub type CommandResult = Result<(), CommandError>;
+
+#[derive(Debug, Display)]
+pub enum CommandError {
+    // shown to the member as is
+    #[display(fmt = "{}", _0)]
+    Message(String),
+    #[display(fmt = "Something went wrong, please try again later")]
+    Internal(Box<dyn Error + Send + Sync>),
+}
+
+impl CommandError {
+    pub fn message<S: ToString>(message: S) -> Self {
+        CommandError::Message(message.to_string())
+    }
+}
+
+impl From<sqlx::Error> for CommandError {
+    fn from(e: sqlx::Error) -> Self {
+        CommandError::Internal(Box::new(e))
+    }
+}
+
+impl From<vrsc_rpc::Error> for CommandError {
+    fn from(e: vrsc_rpc::Error) -> Self {
+        CommandError::Internal(Box::new(e))
+    }
+}
+
+impl From<ArweaveError> for CommandError {
+    fn from(e: ArweaveError) -> Self {
+        CommandError::Internal(Box::new(e))
+    }
+}
+
+impl From<serde_json::Error> for CommandError {
+    fn from(e: serde_json::Error) -> Self {
+        CommandError::Internal(Box::new(e))
+    }
+}
+
+impl From<serenity::Error> for CommandError {
+    fn from(e: serenity::Error) -> Self {
+        CommandError::Internal(Box::new(e))
+    }
+}
+
+// Discord shows at most 25 suggestions
+const CHOICES_SHOWN: usize = 25;
+
+/// A suggestion for an integer option while the member is typing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice {
+    pub name: String,
+    pub value: i64,
+}
+
+/// How a command acknowledges its interaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Acknowledge {
+    // the command creates the response itself
+    Respond,
+    // the registry defers the response and the command edits it with `edit_response`
+    Defer { ephemeral: bool },
+}
+
+#[async_trait]
+pub trait SlashCommand: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The description and options; the name is already set.
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand;
+
+    // only for the operators of the bot, see `is_operator`
+    fn admin_only(&self) -> bool {
+        false
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Respond
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult;
+
+    /// Suggestions for the focused option, for options created with `set_autocomplete(true)`.
+    async fn autocomplete(
+        &self,
+        _ctx: &Context,
+        _autocomplete: &AutocompleteInteraction,
+        _app_config: &Settings,
+    ) -> Result<Vec<Choice>, CommandError> {
+        Ok(vec![])
+    }
+
+    /// The prefix of the `custom_id` of the buttons this command handles in `component`. A button
+    /// is always deferred with an ephemeral response, which `component` edits once it is done.
+    fn component_prefix(&self) -> Option<&'static str> {
+        None
+    }
+
+    async fn component(
+        &self,
+        _ctx: &Context,
+        _component: &MessageComponentInteraction,
+        _app_config: &Settings,
+        _client: &Client,
+    ) -> CommandResult {
+        Ok(())
+    }
+}
+
+pub struct Registry {
+    commands: Vec<Box<dyn SlashCommand>>,
+}
+
+impl Registry {
+    pub fn new(commands: Vec<Box<dyn SlashCommand>>) -> Self {
+        Registry { commands }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.commands.iter().map(|command| command.name())
+    }
+
+    /// Replaces the commands of the server with the ones in the registry. Admin commands are hidden
+    /// from members without the Administrator permission, unless operators are configured: Discord
+    /// cannot hide them by role from here, so `dispatch` checks who uses them.
+    pub async fn register(
+        &self,
+        ctx: &Context,
+        guild_id: GuildId,
+        app_config: &Settings,
+    ) -> Result<Vec<Command>, serenity::Error> {
+        guild_id
+            .set_application_commands(&ctx.http, |commands| {
+                for slash_command in &self.commands {
+                    commands.create_application_command(|command| {
+                        command.name(slash_command.name());
+                        if slash_command.admin_only() && !app_config.admin.is_configured() {
+                            command.default_member_permissions(Permissions::ADMINISTRATOR);
+                        }
+                        slash_command.create(command)
+                    });
+                }
+                commands
+            })
+            .await
+    }
+
+    pub async fn dispatch(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+    ) {
+        let slash_command = match self.commands.iter().find(|c| c.name() == command.data.name) {
+            Some(slash_command) => slash_command,
+            None => {
+                error!("no handler for /{}", command.data.name);
+                return;
+            }
+        };
+
+        if slash_command.admin_only() && !is_operator(command, app_config) {
+            let e = CommandError::message("Only the operators of the bot can use this command");
+            report(ctx, command, e, false).await;
+            return;
+        }
+
+        let deferred = match slash_command.acknowledge() {
+            Acknowledge::Respond => false,
+            Acknowledge::Defer { ephemeral } => {
+                if let Err(e) = defer(ctx, command, ephemeral).await {
+                    error!("could not defer /{}: {:?}", command.data.name, e);
+                    return;
+                }
+                true
+            }
+        };
+
+        let result = match client(app_config) {
+            Ok(client) => slash_command.run(ctx, command, app_config, &client).await,
+            Err(e) => Err(e.into()),
+        };
+
+        if let Err(e) = result {
+            report(ctx, command, e, deferred).await;
+        }
+    }
+
+    pub async fn dispatch_autocomplete(
+        &self,
+        ctx: &Context,
+        autocomplete: &AutocompleteInteraction,
+        app_config: &Settings,
+    ) {
+        let slash_command = match self
+            .commands
+            .iter()
+            .find(|c| c.name() == autocomplete.data.name)
+        {
+            Some(slash_command) => slash_command,
+            None => return,
+        };
+
+        // a member who is typing gets no suggestions rather than an error
+        let choices = match slash_command
+            .autocomplete(ctx, autocomplete, app_config)
+            .await
+        {
+            Ok(choices) => choices,
+            Err(e) => {
+                error!(
+                    "autocomplete of /{} failed: {:?}",
+                    autocomplete.data.name, e
+                );
+                vec![]
+            }
+        };
+
+        if let Err(e) = autocomplete
+            .create_autocomplete_response(&ctx.http, |response| {
+                for choice in choices.into_iter().take(CHOICES_SHOWN) {
+                    response.add_int_choice(choice.name, choice.value);
+                }
+                response
+            })
+            .await
+        {
+            error!(
+                "could not suggest options for /{}: {:?}",
+                autocomplete.data.name, e
+            );
+        }
+    }
+
+    pub async fn dispatch_component(
+        &self,
+        ctx: &Context,
+        component: &MessageComponentInteraction,
+        app_config: &Settings,
+    ) {
+        let slash_command = self.commands.iter().find(|c| {
+            c.component_prefix()
+                .map(|prefix| component.data.custom_id.starts_with(prefix))
+                .unwrap_or(false)
+        });
+
+        let slash_command = match slash_command {
+            Some(slash_command) => slash_command,
+            None => return,
+        };
+
+        // taking or cancelling an offer sends a transaction, which takes longer than Discord waits
+        if let Err(e) = component
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                    .interaction_response_data(|data| data.ephemeral(true))
+            })
+            .await
+        {
+            error!("could not defer {}: {:?}", &component.data.custom_id, e);
+            return;
+        }
+
+        let result = match client(app_config) {
+            Ok(client) => {
+                slash_command
+                    .component(ctx, component, app_config, &client)
+                    .await
+            }
+            Err(e) => Err(e.into()),
+        };
+
+        if let Err(e) = result {
+            if let CommandError::Internal(e) = &e {
+                error!("{} failed: {:?}", &component.data.custom_id, e);
+            }
+
+            let content = e.to_string();
+            if let Err(e) = component
+                .edit_original_interaction_response(&ctx.http, |response| {
+                    response.content(&content)
+                })
+                .await
+            {
+                error!("could not respond to a component interaction: {:?}", e);
+            }
+        }
+    }
+}
+
+fn client(app_config: &Settings) -> Result<Client, vrsc_rpc::Error> {
+    match app_config.application.testnet {
+        true => Client::chain("vrsctest", Auth::ConfigFile, None),
+        false => Client::chain("VRSC", Auth::ConfigFile, None),
+    }
+}
+
+async fn defer(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    ephemeral: bool,
+) -> Result<(), serenity::Error> {
+    command
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                .interaction_response_data(|data| data.ephemeral(ephemeral))
+        })
+        .await
+}
+
+/// Replaces the response of a deferred command, which can be done as often as needed to show the
+/// progress.
+pub async fn edit_response<S: ToString>(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    content: S,
+) -> CommandResult {
+    command
+        .edit_original_interaction_response(&ctx.http, |response| {
+            response.content(content.to_string())
+        })
+        .await?;
+
+    Ok(())
+}
+
+// The error goes in the response, or in a follow up when the command already responded.
+async fn report(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    e: CommandError,
+    deferred: bool,
+) {
+    if let CommandError::Internal(e) = &e {
+        error!("/{} failed: {:?}", command.data.name, e);
+    }
+
+    let content = e.to_string();
+    if deferred {
+        if let Err(e) = edit_response(ctx, command, &content).await {
+            error!(
+                "could not report an error of /{}: {:?}",
+                command.data.name, e
+            );
+        }
+        return;
+    }
+
+    if command
+        .create_interaction_response(&ctx.http, |response| {
+            response.interaction_response_data(|data| data.content(&content).ephemeral(true))
+        })
+        .await
+        .is_err()
+    {
+        if let Err(e) = command
+            .create_followup_message(&ctx.http, |message| {
+                message.content(&content).ephemeral(true)
+            })
+            .await
+        {
+            error!(
+                "could not report an error of /{}: {:?}",
+                command.data.name, e
+            );
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// `/search` filters the geckos by trait values, rarity rank, custody and listing status, see
+// `bot::search`.
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::application::{
+        command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+    },
+    prelude::Context,
+};
+use std::str::FromStr;
+use vrsc_rpc::Client;
+
+use super::{
+    boolean_option, database_pool, integer_option, respond,
+    router::{CommandError, CommandResult, SlashCommand},
+    string_option,
+};
+use crate::{
+    bot::{
+        custody::Custody,
+        search::{self, SearchFilter},
+    },
+    configuration::Settings,
+    trader::{format_amount, native_currency},
+};
+
+const RESULTS_SHOWN: usize = 25;
+
+pub struct Search;
+
+#[async_trait]
+impl SlashCommand for Search {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Find Goofy Geckos by traits, rarity, custody or listing")
+            .create_option(|option| {
+                option
+                    .name("traits")
+                    .description("Trait values, e.g. `base:tokay background:4c8358`")
+                    .kind(CommandOptionType::String)
+                    .required(false)
+            })
+            .create_option(|option| {
+                option
+                    .name("min_rank")
+                    .description("The rarest rank to include, 1 is the rarest gecko")
+                    .kind(CommandOptionType::Integer)
+                    .min_int_value(1)
+                    .required(false)
+            })
+            .create_option(|option| {
+                option
+                    .name("max_rank")
+                    .description("The most common rank to include")
+                    .kind(CommandOptionType::Integer)
+                    .min_int_value(1)
+                    .required(false)
+            })
+            .create_option(|option| {
+                option
+                    .name("custody")
+                    .description("Who controls the Goofy Gecko")
+                    .kind(CommandOptionType::String)
+                    .add_string_choice("held by the bot", Custody::Bot.as_str())
+                    .add_string_choice("co-owned (1-of-2)", Custody::CoOwned.as_str())
+                    .add_string_choice("co-owned (2-of-2)", Custody::Multisig.as_str())
+                    .add_string_choice("withdrawn", Custody::Withdrawn.as_str())
+                    .required(false)
+            })
+            .create_option(|option| {
+                option
+                    .name("listed")
+                    .description("Only geckos that are, or are not, listed for sale")
+                    .kind(CommandOptionType::Boolean)
+                    .required(false)
+            })
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        _client: &Client,
+    ) -> CommandResult {
+        let filter = SearchFilter {
+            traits: SearchFilter::parse_traits(string_option(command, "traits").unwrap_or(""))
+                .map_err(CommandError::message)?,
+            min_rank: integer_option(command, "min_rank"),
+            max_rank: integer_option(command, "max_rank"),
+            custody: string_option(command, "custody")
+                .map(Custody::from_str)
+                .transpose()
+                .map_err(CommandError::message)?,
+            listed: boolean_option(command, "listed"),
+        };
+
+        let pool = database_pool(ctx).await;
+        let found = search::search(&pool, &filter).await?;
+        if found.is_empty() {
+            return Err(CommandError::message("No Goofy Geckos match the search"));
+        }
+
+        let currency = native_currency(app_config.application.testnet);
+        let mut lines = found
+            .iter()
+            .take(RESULTS_SHOWN)
+            .map(|gecko| {
+                let mut line = format!("`#{:<5}`", gecko.gecko_number);
+                if let Some(rank) = gecko.rank {
+                    line.push_str(&format!(" rank {}", rank));
+                }
+                if let Some(custody) = gecko.custody {
+                    line.push_str(&format!(" · {}", custody));
+                }
+                if let Some(price) = gecko.price {
+                    line.push_str(&format!(
+                        " · listed at {} {}",
+                        format_amount(price),
+                        currency
+                    ));
+                }
+                line
+            })
+            .collect::<Vec<_>>();
+        if found.len() > RESULTS_SHOWN {
+            lines.push(format!("...and {} more", found.len() - RESULTS_SHOWN));
+        }
+
+        respond(
+            ctx,
+            command,
+            format!(
+                "**{} Goofy Geckos found**\n{}",
+                found.len(),
+                lines.join("\n")
+            ),
+            false,
+        )
+        .await;
+
+        Ok(())
+    }
+}
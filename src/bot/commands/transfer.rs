@@ -0,0 +1,174 @@
+// `/gift`
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::application::{
+        command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+    },
+    prelude::Context,
+};
+use std::str::FromStr;
+use tracing::{debug, error, info};
+use vrsc_rpc::{json::vrsc::Address, Client, RpcApi};
+
+use super::{
+    caller_controls, database_pool, gecko_identity_name, integer_option, market,
+    router::{edit_response, Acknowledge, CommandError, CommandResult, SlashCommand},
+    user_option, vault,
+};
+use crate::{
+    bot::{
+        transfer::{self, Transfer, TransferKind},
+        utils::database,
+    },
+    configuration::Settings,
+    nft::identity::Identity,
+};
+
+pub struct Gift;
+
+#[async_trait]
+impl SlashCommand for Gift {
+    fn name(&self) -> &'static str {
+        "gift"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Gift one of your Goofy Geckos to another member")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to gift")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("member")
+                    .description("The member that receives the Goofy Gecko")
+                    .kind(CommandOptionType::User)
+                    .required(true)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let number = integer_option(command, "number")
+            .ok_or_else(|| CommandError::message("Which gecko do you want to gift?"))?;
+        let recipient = user_option(command, "member")
+            .ok_or_else(|| CommandError::message("Who do you want to gift it to?"))?;
+
+        if recipient.bot || recipient.id == command.user.id {
+            return Err(CommandError::message("You can only gift to another member"));
+        }
+
+        let identity_name = gecko_identity_name(app_config, number);
+        let identity = client.get_identity(&identity_name).map_err(|e| {
+            debug!("{:?}", e);
+            CommandError::message("Identity not found, likely not confirmed on Verus")
+        })?;
+
+        let pool = database_pool(ctx).await;
+        if !caller_controls(
+            &pool,
+            command.user.id.0,
+            &identity.identity.primaryaddresses,
+        )
+        .await
+        {
+            return Err(CommandError::message(format!(
+                "`{}` is not deposited to the bot by you",
+                identity_name
+            )));
+        }
+
+        if identity.identity.minimumsignatures > 1 {
+            return Err(CommandError::message(format!(
+                "`{}` is co-owned 2-of-2, the bot can not transfer it on its own. Use `/coown` with 1-of-2 first.",
+                identity_name
+            )));
+        }
+
+        vault::ensure_unlocked(app_config, &identity_name)?;
+        market::ensure_not_listed(&pool, number, &identity_name).await?;
+
+        // members that did not get a gecko when they joined do not have an address yet.
+        let recipient_address = match database::get_user_address(&pool, recipient.id.0).await? {
+            Some(address) => address,
+            None => {
+                let address = client.get_new_address()?.to_string();
+                database::register_user_address(&pool, recipient.id.0, &address).await?;
+                address
+            }
+        };
+
+        let address = Address::from_str(&recipient_address).map_err(|e| {
+            error!("invalid address in user_register: {:?}", e);
+            CommandError::message("Could not look up the recipient")
+        })?;
+
+        let mut update = Identity::update(&identity_name);
+        update
+            .testnet(app_config.application.testnet)
+            .add_address(&address)
+            .minimum_signatures(1);
+
+        let txid = update.update().await.map_err(|e| {
+            error!("could not gift {}: {:?}", &identity_name, e);
+            CommandError::message(format!("Could not gift `{}`: {}", identity_name, e))
+        })?;
+
+        info!(
+            "{} gifts {} to {}",
+            command.user.tag(),
+            &identity_name,
+            recipient.tag()
+        );
+
+        let from_address = database::get_user_address(&pool, command.user.id.0)
+            .await
+            .ok()
+            .flatten();
+
+        if let Err(e) = transfer::record(
+            &pool,
+            &Transfer {
+                gecko_number: number,
+                identity: identity_name.clone(),
+                kind: TransferKind::Gift,
+                from_discord_user_id: Some(command.user.id.0),
+                to_discord_user_id: Some(recipient.id.0),
+                from_address,
+                to_address: recipient_address,
+                txid: txid.to_string(),
+            },
+        )
+        .await
+        {
+            error!("Database write error: {:?}", e);
+        }
+
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "`{}` is on its way to {}. You will both get a DM once the transfer is confirmed. (txid: {})",
+                identity_name, recipient.tag(), txid
+            ),
+        )
+        .await
+    }
+}
@@ -0,0 +1,195 @@
+// `/vault`
+// Locking a gecko in the Verus Vault protects the owner against the bot: while it is locked, the
+// bot refuses transfers and any update needs to wait for the unlock delay to pass.
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::application::{
+        command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+    },
+    prelude::Context,
+};
+use tracing::{debug, error, info};
+use vrsc_rpc::{Client, RpcApi};
+
+use super::{
+    caller_controls, database_pool, gecko_identity_name, integer_option,
+    router::{edit_response, Acknowledge, CommandError, CommandResult, SlashCommand},
+    string_option,
+};
+use crate::{
+    configuration::Settings,
+    nft::identity::{Identity, Timelock, VaultState},
+};
+
+// about a day worth of blocks
+const DEFAULT_UNLOCK_DELAY: u64 = 1440;
+
+// Bot-initiated transfers are refused while a gecko is in the vault.
+pub(crate) fn ensure_unlocked(app_config: &Settings, identity_name: &str) -> CommandResult {
+    match Identity::vault_state(identity_name, app_config.application.testnet) {
+        Ok(VaultState::Unlocked) => Ok(()),
+        Ok(state) => Err(CommandError::message(format!(
+            "`{}` is in the vault and can not be transferred. {}. Use `/vault` to unlock it.",
+            identity_name, state
+        ))),
+        Err(e) => {
+            error!("could not get vault state of {}: {:?}", identity_name, e);
+            Err(CommandError::message("Could not get the vault state"))
+        }
+    }
+}
+
+pub struct Vault;
+
+#[async_trait]
+impl SlashCommand for Vault {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Lock or unlock your Goofy Gecko in the Verus Vault")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The Goofy Gecko to lock or unlock")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("action")
+                    .description("Lock or unlock")
+                    .kind(CommandOptionType::String)
+                    .add_string_choice("lock", "lock")
+                    .add_string_choice("unlock", "unlock")
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("delay")
+                    .description("Blocks to wait after an unlock, defaults to a day")
+                    .kind(CommandOptionType::Integer)
+                    .required(false)
+            })
+            .create_option(|option| {
+                option
+                    .name("height")
+                    .description("Lock until this block height instead")
+                    .kind(CommandOptionType::Integer)
+                    .required(false)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let number = integer_option(command, "number")
+            .ok_or_else(|| CommandError::message("Which gecko do you want to lock or unlock?"))?;
+
+        let identity_name = gecko_identity_name(app_config, number);
+        let identity = client.get_identity(&identity_name).map_err(|e| {
+            debug!("{:?}", e);
+            CommandError::message("Identity not found, likely not confirmed on Verus")
+        })?;
+
+        let pool = database_pool(ctx).await;
+        if !caller_controls(
+            &pool,
+            command.user.id.0,
+            &identity.identity.primaryaddresses,
+        )
+        .await
+        {
+            return Err(CommandError::message(format!(
+                "`{}` is not deposited to the bot by you",
+                identity_name
+            )));
+        }
+
+        let testnet = app_config.application.testnet;
+        let state = Identity::vault_state(&identity_name, testnet).map_err(|e| {
+            error!("could not get vault state of {}: {:?}", &identity_name, e);
+            CommandError::message("Could not get the vault state")
+        })?;
+
+        let result = match string_option(command, "action") {
+            Some("lock") => {
+                if state.is_locked() {
+                    return Err(CommandError::message(format!(
+                        "`{}` is already in the vault: {}",
+                        identity_name, state
+                    )));
+                }
+
+                let timelock = match integer_option(command, "height") {
+                    Some(height) if height > 0 => {
+                        let tip: u64 = client.call("getblockcount", &[])?;
+                        if height as u64 <= tip {
+                            return Err(CommandError::message(format!(
+                                "Block {} has already passed, the chain is at block {}",
+                                height, tip
+                            )));
+                        }
+
+                        Timelock::UnlockAtBlock(height as u64)
+                    }
+                    _ => Timelock::Delay(
+                        integer_option(command, "delay")
+                            .filter(|delay| *delay > 0)
+                            .map(|delay| delay as u64)
+                            .unwrap_or(DEFAULT_UNLOCK_DELAY),
+                    ),
+                };
+
+                Identity::set_timelock(&identity_name, timelock, testnet)
+            }
+            Some("unlock") => match state {
+                VaultState::Locked { .. } => Identity::unlock(&identity_name, testnet),
+                _ => {
+                    return Err(CommandError::message(format!(
+                        "`{}` is not locked: {}",
+                        identity_name, state
+                    )))
+                }
+            },
+            _ => return Err(CommandError::message("Choose to lock or unlock")),
+        };
+
+        let txid = result.map_err(|e| {
+            error!("could not update vault of {}: {:?}", &identity_name, e);
+            CommandError::message(format!(
+                "Could not update the vault of `{}`: {}",
+                identity_name, e
+            ))
+        })?;
+
+        info!(
+            "{} used the vault on {}",
+            command.user.tag(),
+            &identity_name
+        );
+        edit_response(
+            ctx,
+            command,
+            format!(
+                "The vault of `{}` will be updated once the transaction confirms. (txid: {})",
+                identity_name, txid
+            ),
+        )
+        .await
+    }
+}
@@ -0,0 +1,111 @@
+use serenity::{
+    async_trait,
+    builder::CreateApplicationCommand,
+    model::application::{
+        command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+    },
+    prelude::Context,
+};
+use vrsc_rpc::Client;
+
+use super::{
+    database_pool, gecko_identity_name, integer_option,
+    router::{edit_response, Acknowledge, CommandError, CommandResult, SlashCommand},
+    string_option,
+};
+use crate::{
+    bot::verification::{self, Holder, VerificationError},
+    configuration::Settings,
+};
+
+impl From<VerificationError> for CommandError {
+    fn from(e: VerificationError) -> Self {
+        match e {
+            VerificationError::Database(e) => e.into(),
+            VerificationError::Verus(e) => e.into(),
+            e => CommandError::message(format!("Could not verify: {}", e)),
+        }
+    }
+}
+
+pub struct Verify;
+
+#[async_trait]
+impl SlashCommand for Verify {
+    fn name(&self) -> &'static str {
+        "verify"
+    }
+
+    fn create<'a>(
+        &self,
+        command: &'a mut CreateApplicationCommand,
+    ) -> &'a mut CreateApplicationCommand {
+        command
+            .description("Prove that you control a Goofy Gecko by signing a message")
+            .create_option(|option| {
+                option
+                    .name("number")
+                    .description("The number of the Goofy Gecko")
+                    .kind(CommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("signature")
+                    .description("The signature of the challenge message")
+                    .kind(CommandOptionType::String)
+                    .required(false)
+            })
+            .create_option(|option| {
+                option
+                    .name("signer")
+                    .description("The primary address you signed with, if not the identity")
+                    .kind(CommandOptionType::String)
+                    .required(false)
+            })
+    }
+
+    fn acknowledge(&self) -> Acknowledge {
+        Acknowledge::Defer { ephemeral: true }
+    }
+
+    async fn run(
+        &self,
+        ctx: &Context,
+        command: &ApplicationCommandInteraction,
+        app_config: &Settings,
+        client: &Client,
+    ) -> CommandResult {
+        let number = integer_option(command, "number")
+            .ok_or_else(|| CommandError::message("Which gecko do you want to verify?"))?;
+        let pool = database_pool(ctx).await;
+        let holder = Holder::Discord(command.user.id.0);
+
+        // without a signature a challenge is issued, with one the open challenge is checked
+        let content = match string_option(command, "signature") {
+            None => {
+                let challenge = verification::challenge(&pool, app_config, &holder, number).await?;
+                format!(
+                    "Sign this message with `{}` or one of its primary addresses within 15 minutes:\n```\nsignmessage \"{}\" \"{}\"\n```\nThen run `/verify number:{} signature:<signature>`, adding `signer:<address>` when you signed with an address.",
+                    &challenge.identity, &challenge.identity, &challenge.message, number
+                )
+            }
+            Some(signature) => {
+                let identity = gecko_identity_name(app_config, number);
+                let signer = string_option(command, "signer").unwrap_or(&identity);
+
+                let proof = verification::verify(
+                    &pool, app_config, client, &holder, number, signer, signature,
+                )
+                .await?;
+                format!(
+                    "Verified! You control `{}` until <t:{}:D>",
+                    &proof.identity,
+                    proof.expires_at.timestamp()
+                )
+            }
+        };
+
+        edit_response(ctx, command, content).await
+    }
+}
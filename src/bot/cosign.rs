@@ -0,0 +1,69 @@
+// Updates of geckos that are co-owned 2-of-2 need a signature of the bot and of the user. The bot
+// signs first and keeps track of the half-signed update until the user sends it back completed.
+use sqlx::PgPool;
+
+#[derive(Debug)]
+pub struct PendingSignature {
+    pub id: i64,
+    pub gecko_number: i64,
+    pub identity: String,
+    pub description: String,
+    // the update as the bot signed it
+    pub partial_tx: String,
+}
+
+pub async fn store(
+    pool: &PgPool,
+    gecko_number: i64,
+    identity: &str,
+    discord_user_id: u64,
+    description: &str,
+    partial_tx: &str,
+) -> Result<i64, sqlx::Error> {
+    let record = sqlx::query!(
+        "INSERT INTO pending_signatures (gecko_number, identity, discord_user_id, description, partial_tx, status) VALUES ($1, $2, $3, $4, $5, 'awaiting') RETURNING id",
+        gecko_number,
+        identity,
+        discord_user_id as i64,
+        description,
+        partial_tx
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(record.id)
+}
+
+pub async fn get_awaiting(
+    pool: &PgPool,
+    id: i64,
+    discord_user_id: u64,
+) -> Result<Option<PendingSignature>, sqlx::Error> {
+    let record = sqlx::query!(
+        "SELECT id, gecko_number, identity, description, partial_tx FROM pending_signatures WHERE id = $1 AND discord_user_id = $2 AND status = 'awaiting'",
+        id,
+        discord_user_id as i64
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|r| PendingSignature {
+        id: r.id,
+        gecko_number: r.gecko_number,
+        identity: r.identity,
+        description: r.description,
+        partial_tx: r.partial_tx,
+    }))
+}
+
+pub async fn complete(pool: &PgPool, id: i64, txid: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE pending_signatures SET status = 'completed', completed_txid = $2 WHERE id = $1",
+        id,
+        txid
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+// Keeps track of which geckos the bot has write access to.
+// A gecko is in custody of the bot when one of its primary addresses is an address that the bot
+// generated for a Discord user (`user_register.vrsc_address`). Users deposit a gecko by adding that
+// address to the primary addresses of the gecko and withdraw it by removing the address again.
+use serenity::prelude::Context;
+use sqlx::PgPool;
+use std::{fmt, str::FromStr};
+use tracing::info;
+use vrsc_rpc::json::vrsc::Address;
+
+use crate::bot::utils::dm;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Custody {
+    // only addresses of the bot control the gecko
+    Bot,
+    // the user added an address of their own, but the bot can still act on its own
+    CoOwned,
+    // the user added an address of their own and both need to sign
+    Multisig,
+    // there is no bot address left in the gecko, the bot only has read access
+    Withdrawn,
+}
+
+impl Custody {
+    pub fn from_addresses(
+        primary_addresses: usize,
+        bot_addresses: usize,
+        minimum_signatures: usize,
+    ) -> Self {
+        if bot_addresses == 0 {
+            Custody::Withdrawn
+        } else if bot_addresses == primary_addresses {
+            Custody::Bot
+        } else if minimum_signatures > bot_addresses {
+            Custody::Multisig
+        } else {
+            Custody::CoOwned
+        }
+    }
+
+    // whether the bot can update the gecko without a signature of the user
+    pub fn has_write_access(&self) -> bool {
+        matches!(self, Custody::Bot | Custody::CoOwned)
+    }
+
+    pub fn is_deposited(&self) -> bool {
+        !matches!(self, Custody::Withdrawn)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Custody::Bot => "bot",
+            Custody::CoOwned => "coowned",
+            Custody::Multisig => "multisig",
+            Custody::Withdrawn => "withdrawn",
+        }
+    }
+}
+
+impl fmt::Display for Custody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Custody::Bot => write!(f, "Held by the bot"),
+            Custody::CoOwned => write!(f, "Co-owned (1-of-2)"),
+            Custody::Multisig => write!(f, "Co-owned (2-of-2)"),
+            Custody::Withdrawn => write!(f, "Withdrawn"),
+        }
+    }
+}
+
+impl FromStr for Custody {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bot" => Ok(Custody::Bot),
+            "coowned" => Ok(Custody::CoOwned),
+            "multisig" => Ok(Custody::Multisig),
+            "withdrawn" => Ok(Custody::Withdrawn),
+            other => Err(format!("{} is not a valid custody state", other)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GeckoCustody {
+    pub gecko_number: i64,
+    pub identity: String,
+    pub discord_user_id: Option<u64>,
+    pub custody: Custody,
+}
+
+pub async fn get(pool: &PgPool, gecko_number: i64) -> Result<Option<GeckoCustody>, sqlx::Error> {
+    let record = sqlx::query!(
+        "SELECT gecko_number, identity, discord_user_id, custody FROM gecko_custody WHERE gecko_number = $1",
+        gecko_number
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|r| GeckoCustody {
+        gecko_number: r.gecko_number,
+        identity: r.identity,
+        discord_user_id: r.discord_user_id.map(|id| id as u64),
+        custody: r.custody.parse().unwrap_or(Custody::Withdrawn),
+    }))
+}
+
+pub async fn store(pool: &PgPool, gecko: &GeckoCustody) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO gecko_custody (gecko_number, identity, discord_user_id, custody, updated_at) VALUES ($1, $2, $3, $4, now()) ON CONFLICT (gecko_number) DO UPDATE SET identity = EXCLUDED.identity, discord_user_id = EXCLUDED.discord_user_id, custody = EXCLUDED.custody, updated_at = now()",
+        gecko.gecko_number,
+        gecko.identity,
+        gecko.discord_user_id.map(|id| id as i64),
+        gecko.custody.as_str()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Determines the custody of a gecko from its primary addresses, together with the Discord user
+/// that the first bot address is mapped to.
+pub async fn resolve(
+    pool: &PgPool,
+    primary_addresses: &[Address],
+    minimum_signatures: usize,
+) -> Result<(Custody, Option<u64>), sqlx::Error> {
+    let mut bot_addresses = 0;
+    let mut discord_user_id = None;
+
+    for address in primary_addresses {
+        let record = sqlx::query!(
+            "SELECT discord_user_id FROM user_register WHERE vrsc_address = $1",
+            address.to_string()
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        if let Some(record) = record {
+            bot_addresses += 1;
+            discord_user_id.get_or_insert(record.discord_user_id as u64);
+        }
+    }
+
+    Ok((
+        Custody::from_addresses(primary_addresses.len(), bot_addresses, minimum_signatures),
+        discord_user_id,
+    ))
+}
+
+/// Stores the custody of a gecko after its primary addresses changed, which `indexer` reports.
+/// Users are notified when their gecko is deposited.
+pub(crate) async fn update(
+    ctx: &Context,
+    pool: &PgPool,
+    gecko_number: i64,
+    identity_name: &str,
+    primary_addresses: &[Address],
+    minimum_signatures: usize,
+) -> Result<Custody, sqlx::Error> {
+    let (custody, discord_user_id) = resolve(pool, primary_addresses, minimum_signatures).await?;
+    let previous = get(pool, gecko_number).await?;
+
+    if let Some(previous) = previous.as_ref() {
+        if previous.custody == custody && previous.discord_user_id == discord_user_id {
+            return Ok(custody);
+        }
+    }
+
+    info!(
+        "custody of {} changed from {:?} to {:?}",
+        identity_name,
+        previous.as_ref().map(|p| p.custody),
+        custody
+    );
+
+    store(
+        pool,
+        &GeckoCustody {
+            gecko_number,
+            identity: identity_name.to_string(),
+            discord_user_id,
+            custody,
+        },
+    )
+    .await?;
+
+    let was_deposited = previous.map(|p| p.custody.is_deposited()).unwrap_or(false);
+
+    if !was_deposited && custody.is_deposited() {
+        if let Some(user_id) = discord_user_id {
+            dm::send(
+                ctx,
+                user_id,
+                format!("`{}` has been deposited to the bot!", identity_name),
+            )
+            .await;
+        }
+    }
+
+    Ok(custody)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Custody;
+
+    #[test]
+    fn custody_from_addresses() {
+        assert_eq!(Custody::from_addresses(1, 1, 1), Custody::Bot);
+        assert_eq!(Custody::from_addresses(2, 1, 1), Custody::CoOwned);
+        assert_eq!(Custody::from_addresses(2, 1, 2), Custody::Multisig);
+        assert_eq!(Custody::from_addresses(2, 2, 2), Custody::Bot);
+        assert_eq!(Custody::from_addresses(1, 0, 1), Custody::Withdrawn);
+    }
+
+    #[test]
+    fn custody_roundtrip() {
+        for custody in [
+            Custody::Bot,
+            Custody::CoOwned,
+            Custody::Multisig,
+            Custody::Withdrawn,
+        ] {
+            assert_eq!(custody.as_str().parse::<Custody>(), Ok(custody));
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use serenity::prelude::TypeMapKey;
 use sqlx::PgPool;
 
+use crate::lifecycle::EventBus;
+
 pub struct DatabasePool;
 
 impl TypeMapKey for DatabasePool {
@@ -12,3 +14,9 @@ pub struct AppConfig;
 impl TypeMapKey for AppConfig {
     type Value = crate::configuration::Settings;
 }
+
+pub struct Bus;
+
+impl TypeMapKey for Bus {
+    type Value = EventBus;
+}
@@ -0,0 +1,381 @@
+// Follows the chain block by block and keeps `gecko_ownership` up to date for every sub-ID of the
+// series. Every identity output in a block whose parent is the series is an update of a gecko; when
+// its primary addresses changed, `NftEvent::Transferred` is published.
+use serde_json::{json, Value};
+use serenity::prelude::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::{error::Error, str::FromStr, time::Duration};
+use tracing::{debug, error, info, instrument};
+use vrsc_rpc::{json::vrsc::Address, Auth, Client, RpcApi};
+
+use crate::{
+    bot::{
+        commands::gecko_identity_name,
+        custody,
+        sales::{self, metadata_txid, METADATA_VDXF_KEY},
+    },
+    configuration::Settings,
+    lifecycle::{webhooks, EventBus, NftEvent},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(20);
+const INDEXER_NAME: &str = "ownership";
+// blocks this close to the tip are left for the next round, in case of a reorg
+const REORG_DEPTH: u64 = 2;
+// so a long catch up does not keep the other work waiting
+const MAX_BLOCKS_PER_ROUND: u64 = 500;
+
+/// An identity output of a gecko, as found in a block or returned by `getidentity`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityUpdate {
+    pub gecko_number: i64,
+    pub identity_address: String,
+    pub primary_addresses: Vec<String>,
+    pub minimum_signatures: i32,
+    // the arweave transaction of the metadata, from the contentmap
+    pub metadata_tx: Option<String>,
+    pub txid: String,
+    pub height: u64,
+}
+
+impl IdentityUpdate {
+    // `identity` is the identity object that is found both in `identityprimary` outputs and in the
+    // result of `getidentity`
+    fn from_identity(identity: &Value, txid: &str, height: u64) -> Option<Self> {
+        Some(IdentityUpdate {
+            gecko_number: identity["name"].as_str()?.parse().ok()?,
+            identity_address: identity["identityaddress"].as_str()?.to_string(),
+            primary_addresses: identity["primaryaddresses"]
+                .as_array()?
+                .iter()
+                .filter_map(|address| address.as_str().map(String::from))
+                .collect(),
+            minimum_signatures: identity["minimumsignatures"].as_i64()? as i32,
+            metadata_tx: identity["contentmap"][METADATA_VDXF_KEY]
+                .as_str()
+                .and_then(|hex_tx| metadata_txid(hex_tx).ok()),
+            txid: txid.to_string(),
+            height,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct GeckoOwnership {
+    pub gecko_number: i64,
+    pub identity: String,
+    pub identity_address: String,
+    pub primary_addresses: Vec<String>,
+    pub minimum_signatures: i32,
+    pub update_txid: String,
+    pub last_update_height: i64,
+}
+
+pub async fn get(pool: &PgPool, gecko_number: i64) -> Result<Option<GeckoOwnership>, sqlx::Error> {
+    sqlx::query_as!(
+        GeckoOwnership,
+        "SELECT gecko_number, identity, identity_address, primary_addresses, minimum_signatures, update_txid, last_update_height FROM gecko_ownership WHERE gecko_number = $1",
+        gecko_number
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+async fn store(
+    transaction: &mut Transaction<'_, Postgres>,
+    ownership: &GeckoOwnership,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO gecko_ownership (gecko_number, identity, identity_address, primary_addresses, minimum_signatures, update_txid, last_update_height) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (gecko_number) DO UPDATE SET identity_address = $3, primary_addresses = $4, minimum_signatures = $5, update_txid = $6, last_update_height = $7, updated_at = now()",
+        ownership.gecko_number,
+        ownership.identity,
+        ownership.identity_address,
+        &ownership.primary_addresses,
+        ownership.minimum_signatures,
+        ownership.update_txid,
+        ownership.last_update_height
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+async fn indexed_height(pool: &PgPool) -> Result<Option<u64>, sqlx::Error> {
+    let record = sqlx::query!(
+        "SELECT height FROM indexer_state WHERE name = $1",
+        INDEXER_NAME
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|r| r.height as u64))
+}
+
+async fn set_indexed_height(pool: &PgPool, height: u64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO indexer_state (name, height) VALUES ($1, $2) ON CONFLICT (name) DO UPDATE SET height = $2",
+        INDEXER_NAME,
+        height as i64
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn run(ctx: Context, app_config: Settings, pool: PgPool, bus: EventBus) {
+    loop {
+        if let Err(e) = follow(&ctx, &app_config, &pool, &bus).await {
+            error!("indexing failed: {:?}", e);
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[instrument(skip_all)]
+async fn follow(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+    bus: &EventBus,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = match app_config.application.testnet {
+        true => Client::chain("vrsctest", Auth::ConfigFile, None),
+        false => Client::chain("VRSC", Auth::ConfigFile, None),
+    }?;
+
+    let series = client
+        .get_identity(&format!("{}@", app_config.application.series))?
+        .identity
+        .identityaddress
+        .to_string();
+    let tip: u64 = client.call("getblockcount", &[])?;
+    let target = tip.saturating_sub(REORG_DEPTH);
+
+    let from = match indexed_height(pool).await? {
+        Some(height) => height + 1,
+        None => {
+            // the first run starts from the current state of every gecko
+            seed(ctx, app_config, pool, &client).await?;
+            set_indexed_height(pool, target).await?;
+            return Ok(());
+        }
+    };
+
+    for height in from..=target.min(from + MAX_BLOCKS_PER_ROUND) {
+        let hash: String = client.call("getblockhash", &[json!(height)])?;
+        let block: Value = client.call("getblock", &[json!(hash), json!(2)])?;
+
+        for update in identity_updates(&block, &series, height) {
+            apply(ctx, app_config, pool, Some(bus), update).await?;
+        }
+
+        set_indexed_height(pool, height).await?;
+    }
+
+    Ok(())
+}
+
+// Every gecko that was minted so far, numbered from `sequence_start`.
+async fn seed(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+    client: &Client,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let next = sqlx::query!(
+        "SELECT last_value + CASE WHEN is_called THEN 1 ELSE 0 END AS next FROM goofygeckoserial"
+    )
+    .fetch_one(pool)
+    .await?
+    .next
+    .unwrap_or(1);
+    let minted = next - 1;
+    let first = app_config.application.sequence_start as i64 + 1;
+    info!("seeding the ownership of {} geckos", minted);
+
+    for number in first..first + minted {
+        let identity_name = gecko_identity_name(app_config, number);
+        let response: Value = match client.call("getidentity", &[json!(identity_name)]) {
+            Ok(response) => response,
+            Err(e) => {
+                debug!("could not get identity {}: {:?}", &identity_name, e);
+                continue;
+            }
+        };
+
+        let update = IdentityUpdate::from_identity(
+            &response["identity"],
+            response["txid"].as_str().unwrap_or_default(),
+            response["blockheight"].as_u64().unwrap_or_default(),
+        );
+
+        if let Some(update) = update {
+            apply(ctx, app_config, pool, None, update).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the updates of the sub-IDs of `series` in a block that was fetched with verbosity 2.
+pub fn identity_updates(block: &Value, series: &str, height: u64) -> Vec<IdentityUpdate> {
+    let transactions = match block["tx"].as_array() {
+        Some(transactions) => transactions,
+        None => return vec![],
+    };
+
+    transactions
+        .iter()
+        .flat_map(|tx| {
+            let txid = tx["txid"].as_str().unwrap_or_default();
+            tx["vout"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|vout| &vout["scriptPubKey"]["identityprimary"])
+                .filter(|identity| identity["parent"].as_str() == Some(series))
+                .filter_map(move |identity| IdentityUpdate::from_identity(identity, txid, height))
+        })
+        .collect()
+}
+
+// Nothing is reported while seeding, when there is no previous state to compare with.
+async fn apply(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+    bus: Option<&EventBus>,
+    update: IdentityUpdate,
+) -> Result<(), sqlx::Error> {
+    let identity_name = gecko_identity_name(app_config, update.gecko_number);
+    let previous = get(pool, update.gecko_number).await?;
+
+    let addresses = update
+        .primary_addresses
+        .iter()
+        .filter_map(|address| Address::from_str(address).ok())
+        .collect::<Vec<_>>();
+    custody::update(
+        ctx,
+        pool,
+        update.gecko_number,
+        &identity_name,
+        &addresses,
+        update.minimum_signatures as usize,
+    )
+    .await?;
+
+    // a transfer is stored together with its webhook deliveries
+    let transfer = match previous {
+        Some(previous)
+            if bus.is_some() && previous.primary_addresses != update.primary_addresses =>
+        {
+            info!(
+                "Transfer({:?}, {:?}, {})",
+                &previous.primary_addresses, &update.primary_addresses, &identity_name
+            );
+
+            Some(NftEvent::Transferred {
+                gecko_number: update.gecko_number,
+                identity: identity_name.clone(),
+                from: previous.primary_addresses,
+                to: update.primary_addresses.clone(),
+                metadata_tx: update.metadata_tx.clone(),
+                txid: update.txid.clone(),
+                height: update.height,
+            })
+        }
+        _ => None,
+    };
+
+    let mut transaction = pool.begin().await?;
+    store(
+        &mut transaction,
+        &GeckoOwnership {
+            gecko_number: update.gecko_number,
+            identity: identity_name.clone(),
+            identity_address: update.identity_address.clone(),
+            primary_addresses: update.primary_addresses.clone(),
+            minimum_signatures: update.minimum_signatures,
+            update_txid: update.txid.clone(),
+            last_update_height: update.height as i64,
+        },
+    )
+    .await?;
+    if let Some(transfer) = &transfer {
+        webhooks::enqueue(&mut transaction, &app_config.events, transfer).await?;
+    }
+    transaction.commit().await?;
+
+    // the role rules and the trait floors need the traits of every gecko
+    if let Err(e) = sales::ensure_traits(
+        pool,
+        update.gecko_number,
+        &identity_name,
+        update.metadata_tx.as_deref(),
+    )
+    .await
+    {
+        error!("could not store traits of {}: {:?}", &identity_name, e);
+    }
+
+    if let (Some(transfer), Some(bus)) = (transfer, bus) {
+        bus.publish(transfer);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::identity_updates;
+    use serde_json::json;
+
+    #[test]
+    fn finds_updates_of_the_series() {
+        let block = json!({
+            "tx": [
+                {
+                    "txid": "aa",
+                    "vout": [
+                        { "scriptPubKey": { "addresses": ["RXYZ"] } },
+                        { "scriptPubKey": { "identityprimary": {
+                            "name": "12",
+                            "parent": "iSeries",
+                            "identityaddress": "iGecko12",
+                            "primaryaddresses": ["RNewOwner"],
+                            "minimumsignatures": 1
+                        } } }
+                    ]
+                },
+                {
+                    "txid": "bb",
+                    "vout": [
+                        { "scriptPubKey": { "identityprimary": {
+                            "name": "someone",
+                            "parent": "iOther",
+                            "identityaddress": "iSomeone",
+                            "primaryaddresses": ["RSomeone"],
+                            "minimumsignatures": 1
+                        } } }
+                    ]
+                }
+            ]
+        });
+
+        let updates = identity_updates(&block, "iSeries", 100);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].gecko_number, 12);
+        assert_eq!(updates[0].primary_addresses, vec!["RNewOwner".to_string()]);
+        assert_eq!(updates[0].txid, "aa");
+        assert_eq!(updates[0].height, 100);
+    }
+
+    #[test]
+    fn ignores_blocks_without_transactions() {
+        assert!(identity_updates(&json!({}), "iSeries", 1).is_empty());
+    }
+}
@@ -0,0 +1,882 @@
+// Offers that were made through the bot. The offers themselves live on chain, the `listings`
+// (asks) and `bids` tables remember who made them and where they were posted in the market
+// channel, so the posts can be updated once an offer is taken, cancelled or expires.
+use serenity::{
+    builder::CreateEmbed,
+    model::{
+        application::component::ButtonStyle,
+        id::{ChannelId, MessageId},
+    },
+    prelude::Context,
+};
+use sqlx::PgPool;
+use std::{error::Error, str::FromStr, time::Duration};
+use tracing::{debug, error, info, instrument};
+use vrsc_rpc::{bitcoin::Txid, json::vrsc::Address, Auth, Client, RpcApi};
+
+use crate::{
+    bot::{
+        commands::{caller_controls, event_bus, gecko_identity_name},
+        custody,
+        sales::{self, Sale},
+        transfer::{self, Transfer, TransferKind},
+        utils::database,
+    },
+    configuration::Settings,
+    lifecycle::NftEvent,
+    nft::identity::Identity,
+    trader::{format_amount, Marketplace, Offer, OfferSide},
+};
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone)]
+pub struct MarketOffer {
+    pub txid: String,
+    pub side: OfferSide,
+    pub gecko_number: i64,
+    pub identity: String,
+    // the member that made the offer and the address it pays from or to
+    pub discord_user_id: u64,
+    pub vrsc_address: String,
+    // in satoshis of `currency`
+    pub price: u64,
+    pub currency: String,
+    pub expiry_height: Option<u64>,
+    pub channel_id: Option<u64>,
+    pub message_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OfferStatus {
+    Open,
+    Filled,
+    Cancelled,
+    Expired,
+    // the seller can no longer deliver the gecko
+    Invalidated,
+}
+
+impl OfferStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OfferStatus::Open => "open",
+            OfferStatus::Filled => "filled",
+            OfferStatus::Cancelled => "cancelled",
+            OfferStatus::Expired => "expired",
+            OfferStatus::Invalidated => "invalidated",
+        }
+    }
+}
+
+// `listings` and `bids` have the same columns
+struct OfferRecord {
+    txid: String,
+    gecko_number: i64,
+    identity: String,
+    discord_user_id: i64,
+    vrsc_address: String,
+    price: i64,
+    currency: String,
+    expiry_height: Option<i64>,
+    channel_id: Option<i64>,
+    message_id: Option<i64>,
+}
+
+impl OfferRecord {
+    fn into_offer(self, side: OfferSide) -> MarketOffer {
+        MarketOffer {
+            txid: self.txid,
+            side,
+            gecko_number: self.gecko_number,
+            identity: self.identity,
+            discord_user_id: self.discord_user_id as u64,
+            vrsc_address: self.vrsc_address,
+            price: self.price as u64,
+            currency: self.currency,
+            expiry_height: self.expiry_height.map(|h| h as u64),
+            channel_id: self.channel_id.map(|id| id as u64),
+            message_id: self.message_id.map(|id| id as u64),
+        }
+    }
+}
+
+pub async fn store(pool: &PgPool, offer: &MarketOffer) -> Result<(), sqlx::Error> {
+    let expiry_height = offer.expiry_height.map(|h| h as i64);
+
+    match offer.side {
+        OfferSide::Ask => {
+            sqlx::query!(
+                "INSERT INTO listings (txid, gecko_number, identity, discord_user_id, vrsc_address, price, currency, expiry_height, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'open')",
+                offer.txid,
+                offer.gecko_number,
+                offer.identity,
+                offer.discord_user_id as i64,
+                offer.vrsc_address,
+                offer.price as i64,
+                offer.currency,
+                expiry_height
+            )
+            .execute(pool)
+            .await?;
+        }
+        OfferSide::Bid => {
+            sqlx::query!(
+                "INSERT INTO bids (txid, gecko_number, identity, discord_user_id, vrsc_address, price, currency, expiry_height, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'open')",
+                offer.txid,
+                offer.gecko_number,
+                offer.identity,
+                offer.discord_user_id as i64,
+                offer.vrsc_address,
+                offer.price as i64,
+                offer.currency,
+                expiry_height
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn set_message(
+    pool: &PgPool,
+    side: OfferSide,
+    txid: &str,
+    channel_id: u64,
+    message_id: u64,
+) -> Result<(), sqlx::Error> {
+    match side {
+        OfferSide::Ask => {
+            sqlx::query!(
+                "UPDATE listings SET channel_id = $2, message_id = $3 WHERE txid = $1",
+                txid,
+                channel_id as i64,
+                message_id as i64
+            )
+            .execute(pool)
+            .await?;
+        }
+        OfferSide::Bid => {
+            sqlx::query!(
+                "UPDATE bids SET channel_id = $2, message_id = $3 WHERE txid = $1",
+                txid,
+                channel_id as i64,
+                message_id as i64
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Looks up an open offer that was made through the bot, whether it is an ask or a bid.
+pub async fn get_open(pool: &PgPool, txid: &str) -> Result<Option<MarketOffer>, sqlx::Error> {
+    let ask = sqlx::query_as!(
+        OfferRecord,
+        "SELECT txid, gecko_number, identity, discord_user_id, vrsc_address, price, currency, expiry_height, channel_id, message_id FROM listings WHERE txid = $1 AND status = 'open'",
+        txid
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(ask) = ask {
+        return Ok(Some(ask.into_offer(OfferSide::Ask)));
+    }
+
+    let bid = sqlx::query_as!(
+        OfferRecord,
+        "SELECT txid, gecko_number, identity, discord_user_id, vrsc_address, price, currency, expiry_height, channel_id, message_id FROM bids WHERE txid = $1 AND status = 'open'",
+        txid
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(bid.map(|bid| bid.into_offer(OfferSide::Bid)))
+}
+
+/// The open ask on a gecko that was made through the bot, if it is listed.
+pub async fn open_listing(
+    pool: &PgPool,
+    gecko_number: i64,
+) -> Result<Option<MarketOffer>, sqlx::Error> {
+    let ask = sqlx::query_as!(
+        OfferRecord,
+        "SELECT txid, gecko_number, identity, discord_user_id, vrsc_address, price, currency, expiry_height, channel_id, message_id FROM listings WHERE gecko_number = $1 AND status = 'open' ORDER BY created_at LIMIT 1",
+        gecko_number
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(ask.map(|ask| ask.into_offer(OfferSide::Ask)))
+}
+
+pub async fn all_open(pool: &PgPool) -> Result<Vec<MarketOffer>, sqlx::Error> {
+    let asks = sqlx::query_as!(
+        OfferRecord,
+        "SELECT txid, gecko_number, identity, discord_user_id, vrsc_address, price, currency, expiry_height, channel_id, message_id FROM listings WHERE status = 'open'"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let bids = sqlx::query_as!(
+        OfferRecord,
+        "SELECT txid, gecko_number, identity, discord_user_id, vrsc_address, price, currency, expiry_height, channel_id, message_id FROM bids WHERE status = 'open'"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(asks
+        .into_iter()
+        .map(|ask| ask.into_offer(OfferSide::Ask))
+        .chain(bids.into_iter().map(|bid| bid.into_offer(OfferSide::Bid)))
+        .collect())
+}
+
+/// Finds the offer that was taken by the sale in `fill_txid`.
+pub async fn get_filled_by(
+    pool: &PgPool,
+    fill_txid: &str,
+) -> Result<Option<MarketOffer>, sqlx::Error> {
+    let ask = sqlx::query_as!(
+        OfferRecord,
+        "SELECT txid, gecko_number, identity, discord_user_id, vrsc_address, price, currency, expiry_height, channel_id, message_id FROM listings WHERE fill_txid = $1",
+        fill_txid
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(ask) = ask {
+        return Ok(Some(ask.into_offer(OfferSide::Ask)));
+    }
+
+    let bid = sqlx::query_as!(
+        OfferRecord,
+        "SELECT txid, gecko_number, identity, discord_user_id, vrsc_address, price, currency, expiry_height, channel_id, message_id FROM bids WHERE fill_txid = $1",
+        fill_txid
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(bid.map(|bid| bid.into_offer(OfferSide::Bid)))
+}
+
+async fn set_status(
+    pool: &PgPool,
+    offer: &MarketOffer,
+    status: OfferStatus,
+    fill_txid: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    match offer.side {
+        OfferSide::Ask => {
+            sqlx::query!(
+                "UPDATE listings SET status = $2, fill_txid = $3, closed_at = now() WHERE txid = $1",
+                offer.txid,
+                status.as_str(),
+                fill_txid
+            )
+            .execute(pool)
+            .await?;
+        }
+        OfferSide::Bid => {
+            sqlx::query!(
+                "UPDATE bids SET status = $2, fill_txid = $3, closed_at = now() WHERE txid = $1",
+                offer.txid,
+                status.as_str(),
+                fill_txid
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn offer_embed<'a>(embed: &'a mut CreateEmbed, offer: &MarketOffer) -> &'a mut CreateEmbed {
+    let (title, maker) = match offer.side {
+        OfferSide::Ask => (
+            format!("Goofy Gecko #{} is for sale", offer.gecko_number),
+            "Seller",
+        ),
+        OfferSide::Bid => (
+            format!("Bid on Goofy Gecko #{}", offer.gecko_number),
+            "Bidder",
+        ),
+    };
+
+    embed
+        .title(title)
+        .field(
+            "Price",
+            format!("{} {}", format_amount(offer.price), offer.currency),
+            true,
+        )
+        .field(maker, format!("<@{}>", offer.discord_user_id), true)
+        .field("Offer", format!("`{}`", offer.txid), false);
+
+    if let Some(height) = offer.expiry_height {
+        embed.field("Expires at block", height, true);
+    }
+
+    embed
+}
+
+/// Posts a new offer in the market channel, with buttons to take or cancel it.
+pub async fn post(ctx: &Context, app_config: &Settings, pool: &PgPool, offer: &MarketOffer) {
+    let channel_id = match app_config.market.channel_id {
+        Some(channel_id) => ChannelId(channel_id),
+        None => return,
+    };
+
+    let take_label = match offer.side {
+        OfferSide::Ask => "Buy",
+        OfferSide::Bid => "Sell",
+    };
+
+    let message = channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| offer_embed(e, offer)).components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|b| {
+                        b.custom_id(format!("market:take:{}", offer.txid))
+                            .label(take_label)
+                            .style(ButtonStyle::Success)
+                    })
+                    .create_button(|b| {
+                        b.custom_id(format!("market:cancel:{}", offer.txid))
+                            .label("Cancel")
+                            .style(ButtonStyle::Secondary)
+                    })
+                })
+            })
+        })
+        .await;
+
+    match message {
+        Ok(message) => {
+            if let Err(e) =
+                set_message(pool, offer.side, &offer.txid, channel_id.0, message.id.0).await
+            {
+                error!("Database write error: {:?}", e);
+            }
+        }
+        Err(e) => error!("could not post offer {}: {:?}", &offer.txid, e),
+    }
+}
+
+/// Opens the offer that the sale in `fill_txid` took again, after that sale failed to go through,
+/// and posts it again. `reconcile` closes it if it is gone from the chain in the meantime.
+pub async fn reopen(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+    fill_txid: &str,
+) -> Result<(), sqlx::Error> {
+    let offer = match get_filled_by(pool, fill_txid).await? {
+        Some(offer) => offer,
+        None => return Ok(()),
+    };
+
+    match offer.side {
+        OfferSide::Ask => {
+            sqlx::query!(
+                "UPDATE listings SET status = 'open', fill_txid = NULL, closed_at = NULL WHERE txid = $1",
+                offer.txid
+            )
+            .execute(pool)
+            .await?;
+        }
+        OfferSide::Bid => {
+            sqlx::query!(
+                "UPDATE bids SET status = 'open', fill_txid = NULL, closed_at = NULL WHERE txid = $1",
+                offer.txid
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+    info!("offer {} is open again", &offer.txid);
+
+    retire_post(
+        ctx,
+        &offer,
+        "The sale did not go through, the offer is posted again",
+    )
+    .await;
+    post(ctx, app_config, pool, &offer).await;
+
+    Ok(())
+}
+
+// Replaces the buttons of a posted offer with the reason it was closed.
+async fn retire_post(ctx: &Context, offer: &MarketOffer, reason: &str) {
+    if let (Some(channel_id), Some(message_id)) = (offer.channel_id, offer.message_id) {
+        if let Err(e) = ChannelId(channel_id)
+            .edit_message(&ctx.http, MessageId(message_id), |m| {
+                m.content(reason).components(|c| c)
+            })
+            .await
+        {
+            error!(
+                "could not update the post of offer {}: {:?}",
+                &offer.txid, e
+            );
+        }
+    }
+}
+
+/// Announces a finished sale in the market channel.
+pub async fn announce_sale(ctx: &Context, app_config: &Settings, offer: &MarketOffer) {
+    let channel_id = match app_config.market.channel_id {
+        Some(channel_id) => ChannelId(channel_id),
+        None => return,
+    };
+
+    if let Err(e) = channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Goofy Gecko #{} was sold for {} {}!",
+                offer.gecko_number,
+                format_amount(offer.price),
+                offer.currency
+            ),
+        )
+        .await
+    {
+        error!("could not announce sale of {}: {:?}", &offer.identity, e);
+    }
+}
+
+/// Takes an open offer on behalf of a member, both for `/accept` and the buttons in the market
+/// channel. Offers that were not made through the bot need the gecko number to be found.
+/// Returns the message for the member.
+pub async fn take(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+    discord_user_id: u64,
+    txid: &str,
+    gecko_number: Option<i64>,
+) -> Result<String, String> {
+    let tracked = get_open(pool, txid).await.map_err(|e| {
+        error!("Database read error: {:?}", e);
+        String::from("Could not look up the offer")
+    })?;
+
+    let gecko_number = match (&tracked, gecko_number) {
+        (Some(offer), _) => offer.gecko_number,
+        (None, Some(n)) => n,
+        (None, None) => {
+            return Err(String::from(
+                "This offer was not made through the bot, add the number of the gecko",
+            ))
+        }
+    };
+
+    if tracked
+        .as_ref()
+        .map(|offer| offer.discord_user_id == discord_user_id)
+        .unwrap_or(false)
+    {
+        return Err(String::from(
+            "You can not take your own offer, use `/cancel` instead",
+        ));
+    }
+
+    let identity_name = gecko_identity_name(app_config, gecko_number);
+    let marketplace = Marketplace::new(app_config.application.testnet).map_err(|e| {
+        error!("{:?}", e);
+        String::from("Could not reach the marketplace")
+    })?;
+
+    let offer = marketplace
+        .offers(&identity_name)
+        .map_err(|e| {
+            error!("could not get offers on {}: {:?}", &identity_name, e);
+            String::from("Could not reach the marketplace")
+        })?
+        .find(txid)
+        .cloned()
+        .ok_or_else(|| format!("`{}` is not an open offer on `{}`", txid, identity_name))?;
+
+    let taker_address = match database::get_user_address(pool, discord_user_id).await {
+        Ok(Some(address)) => Address::from_str(&address).map_err(|e| {
+            error!("invalid address in user_register: {:?}", e);
+            String::from("Could not look up your address")
+        })?,
+        Ok(None) => {
+            return Err(String::from(
+                "You don't have an address with the bot yet, use `/deposit` first",
+            ))
+        }
+        Err(e) => {
+            error!("Database read error: {:?}", e);
+            return Err(String::from("Could not look up your address"));
+        }
+    };
+
+    // for a bid the taker delivers the gecko, which the bot must be able to do on their behalf
+    if offer.side == OfferSide::Bid {
+        let client = match app_config.application.testnet {
+            true => Client::chain("vrsctest", Auth::ConfigFile, None),
+            false => Client::chain("VRSC", Auth::ConfigFile, None),
+        }
+        .map_err(|e| {
+            error!("{:?}", e);
+            String::from("Could not reach the Verus daemon")
+        })?;
+
+        let identity = client.get_identity(&identity_name).map_err(|e| {
+            debug!("{:?}", e);
+            String::from("Identity not found, likely not confirmed on Verus")
+        })?;
+
+        if !caller_controls(pool, discord_user_id, &identity.identity.primaryaddresses).await
+            || identity.identity.minimumsignatures > 1
+        {
+            return Err(format!(
+                "`{}` is not deposited to the bot by you",
+                identity_name
+            ));
+        }
+
+        match Identity::vault_state(&identity_name, app_config.application.testnet) {
+            Ok(state) if state.is_locked() => {
+                return Err(format!(
+                    "`{}` is in the vault and can not be sold. {}.",
+                    identity_name, state
+                ))
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("could not get vault state of {}: {:?}", &identity_name, e);
+                return Err(String::from("Could not get the vault state"));
+            }
+        }
+    }
+
+    let fill_txid = marketplace.fill(&offer, &taker_address).map_err(|e| {
+        error!("could not take offer {}: {:?}", txid, e);
+        String::from("Could not take the offer, check that you have enough funds")
+    })?;
+    // the swap is sent, so whatever goes wrong from here on should not hide its txid
+    if let Err(e) = record_fill(pool, &offer, gecko_number, &taker_address, &fill_txid).await {
+        error!("could not record the sale in {}: {:?}", fill_txid, e);
+    }
+
+    if let Some(tracked) = tracked {
+        if let Err(e) = set_status(
+            pool,
+            &tracked,
+            OfferStatus::Filled,
+            Some(&fill_txid.to_string()),
+        )
+        .await
+        {
+            error!("Database write error: {:?}", e);
+        }
+        retire_post(ctx, &tracked, "Sold!").await;
+    }
+
+    Ok(format!(
+        "Offer taken, `{}` changes hands once the transaction confirms (txid: {})",
+        identity_name, fill_txid
+    ))
+}
+
+/// Lists a gecko for sale. Only geckos that the bot can update on its own can be listed.
+pub async fn list(
+    pool: &PgPool,
+    marketplace: &Marketplace,
+    gecko_number: i64,
+    identity_name: &str,
+    price: u64,
+    seller_address: &Address,
+    expiry_height: Option<u64>,
+) -> Result<Txid, Box<dyn Error + Send + Sync>> {
+    match custody::get(pool, gecko_number).await? {
+        Some(gecko) if gecko.custody.has_write_access() => {}
+        _ => return Err(format!("`{}` is not held by the bot", identity_name).into()),
+    }
+
+    Ok(marketplace.create_ask(identity_name, price, seller_address, expiry_height)?)
+}
+
+// A taken offer is recorded as a pending sale, which updates the ownership records once it confirms.
+async fn record_fill(
+    pool: &PgPool,
+    offer: &Offer,
+    gecko_number: i64,
+    taker_address: &Address,
+    fill_txid: &Txid,
+) -> Result<(), sqlx::Error> {
+    let taker = database::get_user_by_address(pool, &taker_address.to_string()).await?;
+    let (seller, buyer, buyer_address) = match offer.side {
+        OfferSide::Ask => {
+            let seller = custody::get(pool, gecko_number)
+                .await?
+                .and_then(|gecko| gecko.discord_user_id);
+            (seller, taker, taker_address.to_string())
+        }
+        OfferSide::Bid => {
+            let buyer_address = offer.new_owner.clone().unwrap_or_default();
+            let buyer = database::get_user_by_address(pool, &buyer_address).await?;
+            (taker, buyer, buyer_address)
+        }
+    };
+
+    transfer::record(
+        pool,
+        &Transfer {
+            gecko_number,
+            identity: offer.identity.clone(),
+            kind: TransferKind::Sale,
+            from_discord_user_id: seller,
+            to_discord_user_id: buyer,
+            from_address: None,
+            to_address: buyer_address,
+            txid: fill_txid.to_string(),
+        },
+    )
+    .await?;
+
+    sales::record(
+        pool,
+        &Sale {
+            offer_txid: offer.txid.clone(),
+            txid: Some(fill_txid.to_string()),
+            gecko_number,
+            identity: offer.identity.clone(),
+            side: offer.side,
+            price: offer.price,
+            currency: offer.currency.clone(),
+            seller_discord_user_id: seller,
+            buyer_discord_user_id: buyer,
+        },
+    )
+    .await
+}
+
+/// Cancels an offer that a member made through the bot and returns what was offered.
+pub async fn cancel(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+    discord_user_id: u64,
+    txid: &str,
+) -> Result<String, String> {
+    let offer = match get_open(pool, txid).await {
+        Ok(Some(offer)) => offer,
+        Ok(None) => {
+            return Err(format!(
+                "`{}` is not an open offer that was made through the bot",
+                txid
+            ))
+        }
+        Err(e) => {
+            error!("Database read error: {:?}", e);
+            return Err(String::from("Could not look up the offer"));
+        }
+    };
+
+    if offer.discord_user_id != discord_user_id {
+        return Err(String::from("You can only cancel your own offers"));
+    }
+
+    Marketplace::new(app_config.application.testnet)
+        .and_then(|marketplace| marketplace.close(&[offer.txid.clone()]))
+        .map_err(|e| {
+            error!("could not close offer {}: {:?}", txid, e);
+            String::from("Could not cancel the offer")
+        })?;
+
+    if let Err(e) = set_status(pool, &offer, OfferStatus::Cancelled, None).await {
+        error!("Database write error: {:?}", e);
+    }
+    retire_post(ctx, &offer, "Cancelled").await;
+
+    Ok(format!("Your offer on `{}` is cancelled", offer.identity))
+}
+
+/// Periodically reconciles the open offers in the database with the offers on chain, so the
+/// order book never claims an offer that no longer exists: offers that expired, were taken or
+/// closed outside of the bot, or asks on geckos that left the custody of the seller are closed.
+pub async fn watch(ctx: Context, app_config: Settings, pool: PgPool) {
+    loop {
+        if let Err(e) = reconcile(&ctx, &app_config, &pool).await {
+            error!("reconciling the market failed: {:?}", e);
+        }
+
+        tokio::time::sleep(WATCH_INTERVAL).await;
+    }
+}
+
+// An offer that can not be reconciled is logged and tried again on the next pass, the other offers
+// are reconciled regardless.
+#[instrument(skip_all)]
+async fn reconcile(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = match app_config.application.testnet {
+        true => Client::chain("vrsctest", Auth::ConfigFile, None),
+        false => Client::chain("VRSC", Auth::ConfigFile, None),
+    }?;
+    let marketplace = Marketplace::new(app_config.application.testnet)?;
+    let height = marketplace.block_height()?;
+
+    for offer in all_open(pool).await? {
+        if let Err(e) =
+            reconcile_offer(ctx, app_config, pool, &client, &marketplace, height, &offer).await
+        {
+            error!("could not reconcile offer {}: {:?}", &offer.txid, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn reconcile_offer(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+    client: &Client,
+    marketplace: &Marketplace,
+    height: u64,
+    offer: &MarketOffer,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let listing = match marketplace.offers(&offer.identity) {
+        Ok(listing) => listing,
+        Err(e) => {
+            debug!("could not get offers on {}: {:?}", &offer.identity, e);
+            return Ok(());
+        }
+    };
+    let identity = match client.get_identity(&offer.identity) {
+        Ok(identity) => identity.identity,
+        Err(e) => {
+            debug!("could not get identity {}: {:?}", &offer.identity, e);
+            return Ok(());
+        }
+    };
+    let expired = offer.expiry_height.map(|h| h <= height).unwrap_or(false);
+
+    if listing.find(&offer.txid).is_some() && !expired {
+        if offer.side == OfferSide::Ask {
+            let still_deliverable = match custody::get(pool, offer.gecko_number).await? {
+                Some(gecko) => {
+                    gecko.custody.has_write_access()
+                        && gecko.discord_user_id == Some(offer.discord_user_id)
+                }
+                None => false,
+            };
+
+            if !still_deliverable {
+                info!("closing ask {} on {}", &offer.txid, &offer.identity);
+                marketplace.close(&[offer.txid.clone()])?;
+                set_status(pool, offer, OfferStatus::Invalidated, None).await?;
+                retire_post(ctx, offer, "No longer available").await;
+                return Ok(());
+            }
+
+            // the traits are needed for the per-trait floors
+            let metadata_tx = identity
+                .contentmap
+                .get(sales::METADATA_VDXF_KEY)
+                .and_then(|hex_tx| sales::metadata_txid(hex_tx).ok());
+            if let Err(e) = sales::ensure_traits(
+                pool,
+                offer.gecko_number,
+                &offer.identity,
+                metadata_tx.as_deref(),
+            )
+            .await
+            {
+                error!("could not store traits of {}: {:?}", &offer.identity, e);
+            }
+        }
+
+        return Ok(());
+    }
+
+    // The offer is gone from the chain, or about to be. It was either taken outside of the
+    // bot, or it expired or was closed, in which case whatever was offered is returned. Only the
+    // takeoffer of the offer is a sale: a seller that moved the gecko with `/withdraw` or `/gift`
+    // did not sell it, and an expired offer can not be taken anymore.
+    let fill_txid = match expired {
+        true => None,
+        false => marketplace.taken_by(&offer.txid, &offer.identity)?,
+    };
+
+    if let Some(fill_txid) = fill_txid {
+        info!(
+            "offer {} on {} was taken in {}",
+            &offer.txid, &offer.identity, &fill_txid
+        );
+        set_status(pool, offer, OfferStatus::Filled, Some(&fill_txid)).await?;
+        sales::record(
+            pool,
+            &Sale {
+                offer_txid: offer.txid.clone(),
+                txid: Some(fill_txid.clone()),
+                gecko_number: offer.gecko_number,
+                identity: offer.identity.clone(),
+                side: offer.side,
+                price: offer.price,
+                currency: offer.currency.clone(),
+                seller_discord_user_id: match offer.side {
+                    OfferSide::Ask => Some(offer.discord_user_id),
+                    OfferSide::Bid => None,
+                },
+                buyer_discord_user_id: match offer.side {
+                    OfferSide::Ask => None,
+                    OfferSide::Bid => Some(offer.discord_user_id),
+                },
+            },
+        )
+        .await?;
+        retire_post(ctx, offer, "Sold!").await;
+        announce_sale(ctx, app_config, offer).await;
+
+        event_bus(ctx).await.publish(NftEvent::Sold {
+            gecko_number: offer.gecko_number,
+            identity: offer.identity.clone(),
+            price: offer.price,
+            currency: offer.currency.clone(),
+            offer_txid: offer.txid.clone(),
+            txid: Some(fill_txid),
+        });
+    } else {
+        let owned_by_maker = identity
+            .primaryaddresses
+            .iter()
+            .any(|address| address.to_string() == offer.vrsc_address);
+        let (status, reason) = match (expired, offer.side) {
+            (true, _) => (OfferStatus::Expired, "Expired"),
+            // the gecko left the seller without the offer being taken
+            (false, OfferSide::Ask) if !owned_by_maker => {
+                (OfferStatus::Invalidated, "No longer available")
+            }
+            (false, _) => (OfferStatus::Cancelled, "Cancelled"),
+        };
+        info!(
+            "offer {} on {} is {}",
+            &offer.txid,
+            &offer.identity,
+            status.as_str()
+        );
+        if let Err(e) = marketplace.close(&[offer.txid.clone()]) {
+            debug!("could not close offer {}: {:?}", &offer.txid, e);
+        }
+        set_status(pool, offer, status, None).await?;
+        retire_post(ctx, offer, reason).await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+// A copy of the metadata of every gecko in `gecko_metadata`, with its traits in `gecko_traits`, so
+// lookups do not need a round trip to Arweave. The mint pipeline stores the metadata it uploaded,
+// `backfill` fetches the metadata of geckos that were minted before, and `get` falls back to
+// Arweave for anything that is missing or outdated. Only confirmed Arweave transactions are read, so
+// whatever is fetched is the metadata that is on chain.
+use serde_json::Value;
+use sqlx::PgPool;
+use std::{error::Error, fs, time::Duration};
+use tracing::{debug, error, info, instrument};
+use vrsc_rpc::{Auth, Client, RpcApi};
+
+use crate::{
+    bot::sales::{metadata_txid, METADATA_VDXF_KEY},
+    configuration::Settings,
+    nft::{
+        arweave::{self, ArweaveError},
+        VerusNFT,
+    },
+};
+
+// to not hammer the Arweave gateway
+const BACKFILL_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Display)]
+pub enum MetadataError {
+    #[display(fmt = "database: {}", _0)]
+    Database(sqlx::Error),
+    #[display(fmt = "arweave: {}", _0)]
+    Arweave(ArweaveError),
+    #[display(fmt = "invalid metadata: {}", _0)]
+    Json(serde_json::Error),
+}
+
+impl Error for MetadataError {}
+
+impl From<sqlx::Error> for MetadataError {
+    fn from(e: sqlx::Error) -> Self {
+        MetadataError::Database(e)
+    }
+}
+
+impl From<ArweaveError> for MetadataError {
+    fn from(e: ArweaveError) -> Self {
+        MetadataError::Arweave(e)
+    }
+}
+
+impl From<serde_json::Error> for MetadataError {
+    fn from(e: serde_json::Error) -> Self {
+        MetadataError::Json(e)
+    }
+}
+
+/// Stores the metadata of a gecko and its traits, replacing what was stored before.
+pub async fn store(
+    pool: &PgPool,
+    gecko_number: i64,
+    identity: &str,
+    metadata_tx: &str,
+    body: &Value,
+    confirmed: bool,
+) -> Result<(), sqlx::Error> {
+    let image_tx = body["image"].as_str();
+
+    sqlx::query!(
+        "INSERT INTO gecko_metadata (gecko_number, identity, metadata_tx, image_tx, body, confirmed, confirmed_at) VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $6 THEN now() END) ON CONFLICT (gecko_number) DO UPDATE SET identity = $2, metadata_tx = $3, image_tx = $4, body = $5, confirmed = $6, confirmed_at = CASE WHEN $6 THEN now() END",
+        gecko_number,
+        identity,
+        metadata_tx,
+        image_tx,
+        body.to_string(),
+        confirmed
+    )
+    .execute(pool)
+    .await?;
+
+    let attributes = body["attributes"].as_array().cloned().unwrap_or_default();
+    for attribute in attributes {
+        if let (Some(trait_type), Some(value)) = (
+            attribute["trait_type"].as_str(),
+            attribute["value"].as_str(),
+        ) {
+            sqlx::query!(
+                "INSERT INTO gecko_traits (gecko_number, trait_type, value) VALUES ($1, $2, $3) ON CONFLICT (gecko_number, trait_type) DO UPDATE SET value = $3",
+                gecko_number,
+                trait_type,
+                value
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Stores the metadata the mint pipeline wrote to disk and uploaded. The pipeline waits until the
+/// upload is confirmed, so it is stored as confirmed.
+pub(crate) async fn store_minted(
+    pool: &PgPool,
+    verus_nft: &VerusNFT,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (path, metadata_tx) = match (
+        &verus_nft.generated_metadata_path,
+        &verus_nft.uploaded_metadata_tx_hash,
+    ) {
+        (Some(path), Some(metadata_tx)) => (path, metadata_tx),
+        _ => return Err("the metadata of the gecko was not uploaded".into()),
+    };
+
+    let body = serde_json::from_str::<Value>(&fs::read_to_string(path)?)?;
+    store(
+        pool,
+        verus_nft.sequence as i64,
+        &format!("{}.{}@", verus_nft.sequence, &verus_nft.edition),
+        metadata_tx,
+        &body,
+        true,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// The metadata of a gecko and whether its transaction is confirmed on Arweave. `metadata_tx` is
+/// the arweave transaction in the contentmap of its identity; when the stored metadata is of
+/// another transaction, it is fetched again.
+pub async fn get(
+    pool: &PgPool,
+    gecko_number: i64,
+    identity: &str,
+    metadata_tx: &str,
+) -> Result<(Value, bool), MetadataError> {
+    let cached = sqlx::query!(
+        "SELECT body, confirmed FROM gecko_metadata WHERE gecko_number = $1 AND metadata_tx = $2",
+        gecko_number,
+        metadata_tx
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(cached) = cached {
+        return Ok((serde_json::from_str(&cached.body)?, cached.confirmed));
+    }
+
+    debug!(
+        "metadata of {} is not cached, fetching {}",
+        identity, metadata_tx
+    );
+    let body = arweave::get_metadata_json(metadata_tx).await?;
+    store(pool, gecko_number, identity, metadata_tx, &body, true).await?;
+
+    Ok((body, true))
+}
+
+/// Fetches the metadata of every indexed gecko that has none stored yet, and marks the stored
+/// metadata whose transaction got confirmed since. Returns how many geckos were updated.
+#[instrument(skip_all)]
+pub async fn backfill(
+    app_config: &Settings,
+    pool: &PgPool,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let client = match app_config.application.testnet {
+        true => Client::chain("vrsctest", Auth::ConfigFile, None),
+        false => Client::chain("VRSC", Auth::ConfigFile, None),
+    }?;
+    let mut updated = 0;
+
+    let unconfirmed = sqlx::query!(
+        "SELECT gecko_number, metadata_tx FROM gecko_metadata WHERE NOT confirmed ORDER BY gecko_number"
+    )
+    .fetch_all(pool)
+    .await?;
+    for row in unconfirmed {
+        match arweave::get_transaction_confirmations(&row.metadata_tx).await {
+            Ok(confirmations) if confirmations > 0 => {
+                sqlx::query!(
+                    "UPDATE gecko_metadata SET confirmed = true, confirmed_at = now() WHERE gecko_number = $1",
+                    row.gecko_number
+                )
+                .execute(pool)
+                .await?;
+                updated += 1;
+            }
+            Ok(_) => debug!("metadata of {} is not confirmed yet", row.gecko_number),
+            Err(e) => error!("could not check metadata of {}: {:?}", row.gecko_number, e),
+        }
+        tokio::time::sleep(BACKFILL_DELAY).await;
+    }
+
+    let missing = sqlx::query!(
+        "SELECT o.gecko_number, o.identity FROM gecko_ownership o LEFT JOIN gecko_metadata m ON m.gecko_number = o.gecko_number WHERE m.gecko_number IS NULL ORDER BY o.gecko_number"
+    )
+    .fetch_all(pool)
+    .await?;
+    info!("fetching the metadata of {} geckos", missing.len());
+
+    for row in missing {
+        let metadata_tx = match client.get_identity(&row.identity) {
+            Ok(identity) => identity
+                .identity
+                .contentmap
+                .get(METADATA_VDXF_KEY)
+                .and_then(|hex_tx| metadata_txid(hex_tx).ok()),
+            Err(e) => {
+                error!("could not get identity {}: {:?}", &row.identity, e);
+                continue;
+            }
+        };
+        let metadata_tx = match metadata_tx {
+            Some(metadata_tx) => metadata_tx,
+            None => {
+                debug!("{} has no metadata in its contentmap", &row.identity);
+                continue;
+            }
+        };
+
+        match get(pool, row.gecko_number, &row.identity, &metadata_tx).await {
+            Ok(_) => updated += 1,
+            Err(e) => error!("could not fetch metadata of {}: {:?}", &row.identity, e),
+        }
+        tokio::time::sleep(BACKFILL_DELAY).await;
+    }
+
+    info!("updated the metadata of {} geckos", updated);
+
+    Ok(updated)
+}
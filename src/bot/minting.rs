@@ -0,0 +1,573 @@
+// Every gecko is minted by a job in `mint_jobs`. A job runs the stages of the mint pipeline and
+// stores the result of each stage, so a job that failed is retried from the stage that failed
+// instead of from the start. The bot queues a job when a member joins, or when an operator uses
+// `/admin mint`, and `drain` runs them one at a time; `verusnft mint` runs a job without the bot.
+// Operators can pause the automatic minting on join; jobs that are queued while paused run after
+// `/admin resume`, or together with the next job an operator starts.
+use serde_json::Value;
+use serenity::prelude::Context;
+use sqlx::PgPool;
+use std::{
+    error::Error,
+    fs,
+    path::PathBuf,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tracing::{debug, error, info, instrument};
+use vrsc_rpc::{json::vrsc::Address, Auth, Client, RpcApi};
+
+use crate::{
+    bot::{
+        announcements,
+        custody::{self, Custody, GeckoCustody},
+        global_data::Bus,
+        metadata_cache,
+        utils::database,
+    },
+    configuration::Settings,
+    lifecycle::{webhooks, EventBus, MintStage, NftEvent},
+    nft::VerusNFT,
+};
+
+const PAUSED_FLAG: &str = "minting_paused";
+// the address a dry run mints to when the member has none, instead of a new one from the wallet
+const DRY_RUN_ADDRESS: &str = "RP1sexQNvjGPohJkK9JnuPDH7V7NboycGj";
+
+// jobs run one at a time, so two jobs never mint with the same wallet at once
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    #[display(fmt = "queued")]
+    Queued,
+    #[display(fmt = "running")]
+    Running,
+    #[display(fmt = "failed")]
+    Failed,
+    #[display(fmt = "done")]
+    Done,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Failed => "failed",
+            JobStatus::Done => "done",
+        }
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(JobStatus::Queued),
+            "running" => Ok(JobStatus::Running),
+            "failed" => Ok(JobStatus::Failed),
+            "done" => Ok(JobStatus::Done),
+            other => Err(format!("unknown mint job status `{}`", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MintJob {
+    pub id: i64,
+    pub discord_user_id: u64,
+    pub gecko_number: Option<i64>,
+    pub vrsc_address: Option<String>,
+    pub status: JobStatus,
+    // the last stage that completed
+    pub stage: Option<String>,
+    pub metadata_path: Option<String>,
+    pub image_path: Option<String>,
+    pub image_tx: Option<String>,
+    pub metadata_tx: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Queues a mint for a member and returns the id of the job. A member that has a job that did
+/// not finish yet gets no second one.
+pub async fn enqueue(pool: &PgPool, discord_user_id: u64) -> Result<i64, sqlx::Error> {
+    let pending = sqlx::query!(
+        "SELECT id FROM mint_jobs WHERE discord_user_id = $1 AND status IN ('queued', 'running') ORDER BY id LIMIT 1",
+        discord_user_id as i64
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(pending) = pending {
+        return Ok(pending.id);
+    }
+
+    let row = sqlx::query!(
+        "INSERT INTO mint_jobs (discord_user_id) VALUES ($1) RETURNING id",
+        discord_user_id as i64
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row.id)
+}
+
+pub async fn job(pool: &PgPool, id: i64) -> Result<Option<MintJob>, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT id, discord_user_id, gecko_number, vrsc_address, status, stage, metadata_path, image_path, image_tx, metadata_tx, error FROM mint_jobs WHERE id = $1",
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| MintJob {
+        id: row.id,
+        discord_user_id: row.discord_user_id as u64,
+        gecko_number: row.gecko_number,
+        vrsc_address: row.vrsc_address,
+        status: JobStatus::from_str(&row.status).unwrap_or(JobStatus::Failed),
+        stage: row.stage,
+        metadata_path: row.metadata_path,
+        image_path: row.image_path,
+        image_tx: row.image_tx,
+        metadata_tx: row.metadata_tx,
+        error: row.error,
+    }))
+}
+
+/// Whether minting on join is paused by an operator.
+pub async fn is_paused(pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!("SELECT enabled FROM bot_flags WHERE name = $1", PAUSED_FLAG)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| row.enabled).unwrap_or(false))
+}
+
+pub async fn set_paused(pool: &PgPool, paused: bool, operator: u64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO bot_flags (name, enabled, updated_by) VALUES ($1, $2, $3) ON CONFLICT (name) DO UPDATE SET enabled = $2, updated_by = $3, updated_at = now()",
+        PAUSED_FLAG,
+        paused,
+        operator as i64
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Puts a failed job back in the queue, to continue from the stage that failed.
+pub async fn requeue(pool: &PgPool, id: i64) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE mint_jobs SET status = 'queued', error = NULL, updated_at = now() WHERE id = $1 AND status = 'failed'",
+        id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Jobs that were running when the bot stopped will not finish; they are marked as failed so an
+/// operator can retry them.
+pub async fn fail_interrupted(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE mint_jobs SET status = 'failed', error = 'interrupted', updated_at = now() WHERE status = 'running'"
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Runs the queued jobs, oldest first, until the queue is empty. When the queue is already being
+/// drained, the jobs queued since are left to that drain.
+#[instrument(skip_all)]
+pub async fn drain(ctx: Context, app_config: Settings, pool: PgPool) {
+    if DRAINING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let bus = {
+        let data_read = ctx.data.read().await;
+        data_read.get::<Bus>().unwrap().clone()
+    };
+
+    loop {
+        let next = match next_queued(&pool).await {
+            Ok(Some(next)) => next,
+            Ok(None) => {
+                DRAINING.store(false, Ordering::SeqCst);
+                // a job may have been queued right before the flag was cleared
+                match next_queued(&pool).await {
+                    Ok(Some(_)) if !DRAINING.swap(true, Ordering::SeqCst) => continue,
+                    _ => return,
+                }
+            }
+            Err(e) => {
+                error!("could not read the mint queue: {:?}", e);
+                DRAINING.store(false, Ordering::SeqCst);
+                return;
+            }
+        };
+
+        if let Ok(verus_nft) = mint(&app_config, &pool, &bus, next).await {
+            announcements::announce(&ctx, &app_config, &verus_nft).await;
+            announcements::welcome(&ctx, &app_config, &verus_nft).await;
+        }
+    }
+}
+
+/// Runs a mint job and registers the gecko it minted, without announcing it. A job that fails is
+/// marked as failed with the error; a job that is not queued is left as it is.
+pub(crate) async fn mint(
+    app_config: &Settings,
+    pool: &PgPool,
+    bus: &EventBus,
+    id: i64,
+) -> Result<VerusNFT, Box<dyn Error + Send + Sync>> {
+    if !claim(pool, id).await? {
+        return Err(format!("mint job {} is not queued", id).into());
+    }
+
+    match run(app_config, pool, bus, id).await {
+        Ok(verus_nft) => Ok(verus_nft),
+        Err(e) => {
+            error!("mint job {} failed: {}", id, e);
+            if let Err(e) = sqlx::query!(
+                "UPDATE mint_jobs SET status = 'failed', error = $2, updated_at = now() WHERE id = $1",
+                id,
+                e.to_string()
+            )
+            .execute(pool)
+            .await
+            {
+                error!("Database write error: {:?}", e)
+            }
+
+            Err(e)
+        }
+    }
+}
+
+async fn next_queued(pool: &PgPool) -> Result<Option<i64>, sqlx::Error> {
+    let row = sqlx::query!("SELECT id FROM mint_jobs WHERE status = 'queued' ORDER BY id LIMIT 1")
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| row.id))
+}
+
+// `drain` and `verusnft mint` can both pick up a job, only the one that claims it runs it.
+async fn claim(pool: &PgPool, id: i64) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        "UPDATE mint_jobs SET status = 'running', updated_at = now() WHERE id = $1 AND status = 'queued' RETURNING id",
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.is_some())
+}
+
+async fn run(
+    app_config: &Settings,
+    pool: &PgPool,
+    bus: &EventBus,
+    id: i64,
+) -> Result<VerusNFT, Box<dyn Error + Send + Sync>> {
+    let job = match job(pool, id).await? {
+        Some(job) => job,
+        None => return Err(format!("there is no mint job {}", id).into()),
+    };
+
+    let mut verus_nft = resume(app_config, pool, &job).await?;
+    info!(
+        "minting {} nft #{} for {} (job {})",
+        app_config.application.series, verus_nft.sequence, verus_nft.user_id, id
+    );
+    bus.publish(NftEvent::MintStarted {
+        gecko_number: verus_nft.sequence as i64,
+        discord_user_id: verus_nft.user_id,
+    });
+
+    // an identity cannot be created twice, so a job that got that far only needs to finish
+    if job.stage.as_deref() != Some(MintStage::Identity.as_str()) {
+        while let Some(stage) = verus_nft.next_stage() {
+            if !verus_nft.run_stage(stage, app_config, bus).await {
+                return Err(format!("the {} stage failed", stage.as_str()).into());
+            }
+            checkpoint(pool, id, stage, &verus_nft).await?;
+        }
+    }
+
+    verus_nft.finish(app_config, bus).await;
+    register(pool, &verus_nft).await;
+
+    // the job is done together with its webhook deliveries
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        "UPDATE mint_jobs SET status = 'done', updated_at = now() WHERE id = $1",
+        id
+    )
+    .execute(&mut transaction)
+    .await?;
+    webhooks::enqueue(&mut transaction, &app_config.events, &verus_nft.minted()).await?;
+    transaction.commit().await?;
+
+    Ok(verus_nft)
+}
+
+// The gecko number and the address are assigned once, the first time the job runs; the results
+// of the stages that completed before are picked up from the job.
+async fn resume(
+    app_config: &Settings,
+    pool: &PgPool,
+    job: &MintJob,
+) -> Result<VerusNFT, Box<dyn Error + Send + Sync>> {
+    let (gecko_number, vrsc_address) = match (job.gecko_number, job.vrsc_address.as_ref()) {
+        (Some(gecko_number), Some(vrsc_address)) => {
+            (gecko_number, Address::from_str(vrsc_address)?)
+        }
+        _ => {
+            // a member that has an address with the bot already gets the gecko on that address
+            let vrsc_address = match database::get_user_address(pool, job.discord_user_id).await? {
+                Some(vrsc_address) => Address::from_str(&vrsc_address)?,
+                None => {
+                    let vrsc_address = new_address(app_config)?;
+                    if records(app_config.application.dry_run, true).contains(&Record::UserRegister)
+                    {
+                        database::register_user_address(
+                            pool,
+                            job.discord_user_id,
+                            &vrsc_address.to_string(),
+                        )
+                        .await?;
+                    }
+
+                    vrsc_address
+                }
+            };
+
+            let next_gecko_number = sqlx::query!("SELECT nextval('goofygeckoserial')")
+                .fetch_one(pool)
+                .await?
+                .nextval
+                .ok_or("goofygeckoserial has no next value")?;
+            let gecko_number = next_gecko_number + app_config.application.sequence_start as i64;
+            debug!("the next Gecko number is: {}", gecko_number);
+
+            sqlx::query!(
+                "UPDATE mint_jobs SET gecko_number = $2, vrsc_address = $3, updated_at = now() WHERE id = $1",
+                job.id,
+                gecko_number,
+                vrsc_address.to_string()
+            )
+            .execute(pool)
+            .await?;
+
+            (gecko_number, vrsc_address)
+        }
+    };
+
+    let mut verus_nft = VerusNFT::new(
+        job.discord_user_id,
+        gecko_number as u64,
+        vrsc_address,
+        app_config,
+    );
+    verus_nft.generated_metadata_path = job.metadata_path.as_ref().map(PathBuf::from);
+    verus_nft.generated_image_path = job.image_path.as_ref().map(PathBuf::from);
+    verus_nft.uploaded_image_tx_hash = job.image_tx.clone();
+    verus_nft.uploaded_metadata_tx_hash = job.metadata_tx.clone();
+    if let Some(rarity) = verus_nft
+        .generated_metadata_path
+        .as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|metadata| serde_json::from_str::<Value>(&metadata).ok())
+        .and_then(|metadata| metadata["rarity"].as_f64())
+    {
+        verus_nft.rarity = rarity;
+    }
+
+    Ok(verus_nft)
+}
+
+fn new_address(app_config: &Settings) -> Result<Address, Box<dyn Error + Send + Sync>> {
+    if app_config.application.dry_run {
+        info!(
+            "dry run: not calling getnewaddress, simulated as {}",
+            DRY_RUN_ADDRESS
+        );
+        return Ok(Address::from_str(DRY_RUN_ADDRESS)?);
+    }
+
+    let client = match app_config.application.testnet {
+        true => Client::chain("vrsctest", Auth::ConfigFile, None),
+        false => Client::chain("VRSC", Auth::ConfigFile, None),
+    }?;
+
+    Ok(client.get_new_address()?)
+}
+
+/// The tables a job writes to besides `mint_jobs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Record {
+    UserRegister,
+    GeckoCustody,
+    GeckoMetadata,
+}
+
+// A member that is new to the bot is registered with the address they get, and the gecko once it
+// is minted. A dry run made up the address, the identity and the uploads, so it records nothing.
+fn records(dry_run: bool, new_member: bool) -> Vec<Record> {
+    if dry_run {
+        return vec![];
+    }
+
+    let mut records = vec![];
+    if new_member {
+        records.push(Record::UserRegister);
+    }
+    records.extend([Record::GeckoCustody, Record::GeckoMetadata]);
+
+    records
+}
+
+async fn checkpoint(
+    pool: &PgPool,
+    id: i64,
+    stage: MintStage,
+    verus_nft: &VerusNFT,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE mint_jobs SET stage = $2, metadata_path = $3, image_path = $4, image_tx = $5, metadata_tx = $6, updated_at = now() WHERE id = $1",
+        id,
+        stage.as_str(),
+        verus_nft
+            .generated_metadata_path
+            .as_ref()
+            .map(|path| path.display().to_string()),
+        verus_nft
+            .generated_image_path
+            .as_ref()
+            .map(|path| path.display().to_string()),
+        verus_nft.uploaded_image_tx_hash.as_ref(),
+        verus_nft.uploaded_metadata_tx_hash.as_ref()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Registers the gecko that was minted for the member; the member is registered when the job gets
+// its address.
+async fn register(pool: &PgPool, verus_nft: &VerusNFT) {
+    let records = records(verus_nft.dry_run, false);
+    if records.is_empty() {
+        info!(
+            "dry run, not registering {}.{}@",
+            verus_nft.sequence, &verus_nft.edition
+        );
+        return;
+    }
+
+    let gecko = GeckoCustody {
+        gecko_number: verus_nft.sequence as i64,
+        identity: format!("{}.{}@", verus_nft.sequence, verus_nft.edition),
+        discord_user_id: Some(verus_nft.user_id),
+        custody: Custody::Bot,
+    };
+    if records.contains(&Record::GeckoCustody) {
+        if let Err(e) = custody::store(pool, &gecko).await {
+            error!("Database write error: {:?}", e)
+        }
+    }
+
+    if records.contains(&Record::GeckoMetadata) {
+        if let Err(e) = metadata_cache::store_minted(pool, verus_nft).await {
+            error!(
+                "could not cache the metadata of {}: {:?}",
+                &gecko.identity, e
+            )
+        }
+    }
+}
+
+/// How many jobs there are of each status.
+pub async fn counts(pool: &PgPool) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT status, COUNT(*) AS count FROM mint_jobs GROUP BY status ORDER BY status"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.status, row.count.unwrap_or_default()))
+        .collect())
+}
+
+/// The jobs that failed most recently.
+pub async fn recent_failures(pool: &PgPool, limit: i64) -> Result<Vec<MintJob>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT id FROM mint_jobs WHERE status = 'failed' ORDER BY updated_at DESC LIMIT $1",
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut jobs = vec![];
+    for row in rows {
+        if let Some(job) = job(pool, row.id).await? {
+            jobs.push(job);
+        }
+    }
+
+    Ok(jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{records, JobStatus, Record, DRY_RUN_ADDRESS};
+    use std::str::FromStr;
+    use vrsc_rpc::json::vrsc::Address;
+
+    #[test]
+    fn job_statuses_round_trip() {
+        for status in [
+            JobStatus::Queued,
+            JobStatus::Running,
+            JobStatus::Failed,
+            JobStatus::Done,
+        ] {
+            assert_eq!(JobStatus::from_str(status.as_str()), Ok(status));
+            assert_eq!(status.to_string(), status.as_str());
+        }
+        assert!(JobStatus::from_str("minting").is_err());
+    }
+
+    #[test]
+    fn dry_run_records_nothing() {
+        assert!(records(true, true).is_empty());
+        assert!(records(true, false).is_empty());
+        assert!(Address::from_str(DRY_RUN_ADDRESS).is_ok());
+
+        assert_eq!(
+            records(false, true),
+            vec![
+                Record::UserRegister,
+                Record::GeckoCustody,
+                Record::GeckoMetadata
+            ]
+        );
+        assert_eq!(
+            records(false, false),
+            vec![Record::GeckoCustody, Record::GeckoMetadata]
+        );
+    }
+}
@@ -1,4 +1,17 @@
+pub mod announcements;
+pub mod commands;
+pub mod cosign;
+pub mod custody;
 pub mod events;
 pub mod framework;
 pub mod global_data;
+pub mod indexer;
+pub mod market;
+pub mod metadata_cache;
+pub mod minting;
+pub mod roles;
+pub mod sales;
+pub mod search;
+pub mod transfer;
 pub mod utils;
+pub mod verification;
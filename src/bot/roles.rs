@@ -0,0 +1,293 @@
+// Grants and revokes the Discord roles of `roles.rules`. A member holds a gecko when it is deposited
+// with an address of the bot that is mapped to them, or when they proved to control it with
+// `/verify` after its last update. Geckos that are withdrawn without a proof, or sold to someone
+// else, are no longer held, so the roles that depend on them are revoked on the next sync.
+use serenity::{
+    async_trait,
+    model::id::{GuildId, RoleId, UserId},
+    prelude::Context,
+};
+use sqlx::PgPool;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    bot::sales,
+    configuration::{RoleCondition, Settings},
+    lifecycle::{NftEvent, Subscriber},
+};
+
+// the most members Discord returns at once
+const MEMBERS_PER_PAGE: u64 = 1000;
+// events that come in while a sync is scheduled are handled by that sync, so a burst of events,
+// like the catch up of the indexer, scans the members only once
+const SYNC_DELAY: Duration = Duration::from_secs(30);
+
+/// What the role rules are evaluated against.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    // the geckos held by every Discord user
+    pub holdings: HashMap<u64, Vec<i64>>,
+    pub traits: HashMap<i64, HashMap<String, String>>,
+    // 1 is the rarest gecko
+    pub ranks: HashMap<i64, i64>,
+    pub sellers: HashSet<u64>,
+}
+
+impl Snapshot {
+    pub async fn load(pool: &PgPool) -> Result<Self, sqlx::Error> {
+        let mut snapshot = Snapshot::default();
+
+        let holdings = sqlx::query!(
+            "SELECT discord_user_id, gecko_number FROM gecko_custody WHERE discord_user_id IS NOT NULL AND custody <> 'withdrawn' UNION SELECT p.discord_user_id, p.gecko_number FROM ownership_proofs p JOIN gecko_ownership o ON o.gecko_number = p.gecko_number WHERE p.discord_user_id IS NOT NULL AND p.expires_at > now() AND p.verified_at >= o.updated_at"
+        )
+        .fetch_all(pool)
+        .await?;
+        for holding in holdings {
+            if let (Some(user_id), Some(gecko_number)) =
+                (holding.discord_user_id, holding.gecko_number)
+            {
+                snapshot
+                    .holdings
+                    .entry(user_id as u64)
+                    .or_default()
+                    .push(gecko_number);
+            }
+        }
+
+        let traits = sqlx::query!("SELECT gecko_number, trait_type, value FROM gecko_traits")
+            .fetch_all(pool)
+            .await?;
+        for t in traits {
+            snapshot
+                .traits
+                .entry(t.gecko_number)
+                .or_default()
+                .insert(t.trait_type, t.value);
+        }
+
+        snapshot.ranks = sales::ranks(pool).await?;
+
+        snapshot.sellers =
+            sqlx::query!("SELECT DISTINCT discord_user_id FROM listings WHERE status = 'open'")
+                .fetch_all(pool)
+                .await?
+                .into_iter()
+                .map(|seller| seller.discord_user_id as u64)
+                .collect();
+
+        Ok(snapshot)
+    }
+
+    pub fn qualifies(&self, discord_user_id: u64, condition: &RoleCondition) -> bool {
+        let held = self
+            .holdings
+            .get(&discord_user_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        match condition {
+            RoleCondition::Holder { min_count } => held.len() >= *min_count,
+            RoleCondition::Rank { max_rank } => held
+                .iter()
+                .any(|n| self.ranks.get(n).map(|r| r <= max_rank).unwrap_or(false)),
+            RoleCondition::Trait { trait_type, value } => held.iter().any(|n| {
+                self.traits
+                    .get(n)
+                    .and_then(|traits| traits.get(trait_type))
+                    .map(|v| v == value)
+                    .unwrap_or(false)
+            }),
+            RoleCondition::Seller => self.sellers.contains(&discord_user_id),
+        }
+    }
+}
+
+/// Brings the roles of every member of the server in line with the rules.
+#[instrument(skip_all)]
+pub async fn sync(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let rules = &app_config.roles.rules;
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let guild_id = GuildId(app_config.application.discord_guild_id.parse::<u64>()?);
+    let snapshot = Snapshot::load(pool).await?;
+
+    let mut after: Option<UserId> = None;
+    loop {
+        let members = guild_id
+            .members(&ctx.http, Some(MEMBERS_PER_PAGE), after)
+            .await?;
+        let last = members.last().map(|member| member.user.id);
+
+        for mut member in members {
+            if member.user.bot {
+                continue;
+            }
+
+            for rule in rules {
+                let role_id = RoleId(rule.role_id);
+                let has_role = member.roles.contains(&role_id);
+                let qualifies = snapshot.qualifies(member.user.id.0, &rule.condition);
+
+                let result = if qualifies && !has_role {
+                    info!("granting role {} to {}", rule.role_id, member.user.id);
+                    member.add_role(&ctx.http, role_id).await
+                } else if !qualifies && has_role {
+                    info!("revoking role {} from {}", rule.role_id, member.user.id);
+                    member.remove_role(&ctx.http, role_id).await
+                } else {
+                    continue;
+                };
+
+                if let Err(e) = result {
+                    error!(
+                        "could not update role {} of {}: {:?}",
+                        rule.role_id, member.user.id, e
+                    );
+                }
+            }
+        }
+
+        match last {
+            Some(last) => after = Some(last),
+            None => break,
+        }
+    }
+
+    debug!("roles are in sync");
+
+    Ok(())
+}
+
+pub async fn watch(ctx: Context, app_config: Settings, pool: PgPool) {
+    let interval = Duration::from_secs(app_config.roles.sync_interval_minutes.max(1) * 60);
+
+    loop {
+        if let Err(e) = sync(&ctx, &app_config, &pool).await {
+            error!("role sync failed: {:?}", e);
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Syncs the roles shortly after a gecko changes hands or is listed.
+pub struct RoleSync {
+    ctx: Context,
+    app_config: Settings,
+    pool: PgPool,
+    scheduled: Arc<AtomicBool>,
+    // one sync at a time
+    running: Arc<Mutex<()>>,
+}
+
+impl RoleSync {
+    pub fn new(ctx: Context, app_config: Settings, pool: PgPool) -> Self {
+        RoleSync {
+            ctx,
+            app_config,
+            pool,
+            scheduled: Arc::new(AtomicBool::new(false)),
+            running: Arc::new(Mutex::new(())),
+        }
+    }
+}
+
+#[async_trait]
+impl Subscriber for RoleSync {
+    fn name(&self) -> &'static str {
+        "role_sync"
+    }
+
+    fn wants(&self, event: &NftEvent) -> bool {
+        matches!(
+            event,
+            NftEvent::Minted { .. }
+                | NftEvent::Transferred { .. }
+                | NftEvent::Listed { .. }
+                | NftEvent::Sold { .. }
+        )
+    }
+
+    async fn handle(&self, event: &NftEvent) {
+        if self.scheduled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let (ctx, app_config, pool) =
+            (self.ctx.clone(), self.app_config.clone(), self.pool.clone());
+        let (scheduled, running) = (self.scheduled.clone(), self.running.clone());
+        let kind = event.kind();
+        tokio::spawn(async move {
+            tokio::time::sleep(SYNC_DELAY).await;
+            let _running = running.lock().await;
+            // events from here on need another sync, this one may have read the holdings already
+            scheduled.store(false, Ordering::SeqCst);
+
+            if let Err(e) = sync(&ctx, &app_config, &pool).await {
+                error!("role sync after {} failed: {:?}", kind, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Snapshot;
+    use crate::configuration::RoleCondition;
+    use std::collections::HashMap;
+
+    fn snapshot() -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        snapshot.holdings.insert(1, vec![10, 11]);
+        snapshot.holdings.insert(2, vec![12]);
+        snapshot.traits.insert(
+            10,
+            HashMap::from([("_alchemist".to_string(), "alchemist".to_string())]),
+        );
+        snapshot.ranks.insert(10, 40);
+        snapshot.ranks.insert(12, 3);
+        snapshot.sellers.insert(2);
+        snapshot
+    }
+
+    #[test]
+    fn holders_need_enough_geckos() {
+        let snapshot = snapshot();
+
+        assert!(snapshot.qualifies(1, &RoleCondition::Holder { min_count: 2 }));
+        assert!(!snapshot.qualifies(2, &RoleCondition::Holder { min_count: 2 }));
+        assert!(!snapshot.qualifies(3, &RoleCondition::Holder { min_count: 1 }));
+    }
+
+    #[test]
+    fn rank_trait_and_seller() {
+        let snapshot = snapshot();
+        let alchemist = RoleCondition::Trait {
+            trait_type: "_alchemist".to_string(),
+            value: "alchemist".to_string(),
+        };
+
+        assert!(snapshot.qualifies(2, &RoleCondition::Rank { max_rank: 10 }));
+        assert!(!snapshot.qualifies(1, &RoleCondition::Rank { max_rank: 10 }));
+        assert!(snapshot.qualifies(1, &alchemist));
+        assert!(!snapshot.qualifies(2, &alchemist));
+        assert!(snapshot.qualifies(2, &RoleCondition::Seller));
+        assert!(!snapshot.qualifies(1, &RoleCondition::Seller));
+    }
+}
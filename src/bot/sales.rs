@@ -0,0 +1,253 @@
+// The sale history and the statistics that are computed from it together with the open offers in
+// `listings` and `bids`. All prices are in satoshis of the native currency of the chain.
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    PgPool,
+};
+use std::collections::HashMap;
+use tracing::{debug, info};
+
+use crate::{
+    bot::metadata_cache::{self, MetadataError},
+    trader::OfferSide,
+};
+
+// the contentmap key under which the arweave transaction of the metadata is stored
+pub const METADATA_VDXF_KEY: &str = "9a55eaaad7bacc9f37a449e315ff32fedc07b126";
+
+/// The arweave transaction id of a value under `METADATA_VDXF_KEY`, which is stored as hex.
+pub fn metadata_txid(hex_tx: &str) -> Result<String, hex::FromHexError> {
+    Ok(base64_url::encode(&hex::decode(hex_tx)?))
+}
+
+#[derive(Debug, Clone)]
+pub struct Sale {
+    // every sale takes exactly one offer
+    pub offer_txid: String,
+    // unknown when the offer was taken outside of the bot
+    pub txid: Option<String>,
+    pub gecko_number: i64,
+    pub identity: String,
+    // which side the taken offer was on
+    pub side: OfferSide,
+    pub price: u64,
+    pub currency: String,
+    pub seller_discord_user_id: Option<u64>,
+    pub buyer_discord_user_id: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct SalePrice {
+    pub offer_txid: String,
+    pub price: i64,
+    pub currency: String,
+}
+
+#[derive(Debug)]
+pub struct SaleRecord {
+    pub gecko_number: i64,
+    pub identity: String,
+    pub price: i64,
+    pub currency: String,
+    pub buyer_discord_user_id: Option<i64>,
+    pub sold_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct MarketStats {
+    pub floor: Option<u64>,
+    pub listed: i64,
+    pub best_bid: Option<u64>,
+    pub bids: i64,
+    pub sales: i64,
+    pub volume: u64,
+    pub volume_day: u64,
+    pub volume_week: u64,
+    pub last_sale: Option<SaleRecord>,
+}
+
+#[derive(Debug)]
+pub struct TraitFloor {
+    pub value: String,
+    pub floor: u64,
+    pub listed: i64,
+}
+
+/// Stores a sale. A sale that was already recorded for the same offer is left alone.
+pub async fn record(pool: &PgPool, sale: &Sale) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO sales (offer_txid, txid, gecko_number, identity, side, price, currency, seller_discord_user_id, buyer_discord_user_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) ON CONFLICT (offer_txid) DO NOTHING",
+        sale.offer_txid,
+        sale.txid,
+        sale.gecko_number,
+        sale.identity,
+        sale.side.as_str(),
+        sale.price as i64,
+        sale.currency,
+        sale.seller_discord_user_id.map(|id| id as i64),
+        sale.buyer_discord_user_id.map(|id| id as i64)
+    )
+    .execute(pool)
+    .await?;
+
+    info!("{} sold for {} sats", &sale.identity, sale.price);
+
+    Ok(())
+}
+
+// A sale that did not confirm did not happen.
+pub async fn remove(pool: &PgPool, txid: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM sales WHERE txid = $1", txid)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_by_txid(pool: &PgPool, txid: &str) -> Result<Option<SalePrice>, sqlx::Error> {
+    sqlx::query_as!(
+        SalePrice,
+        "SELECT offer_txid, price, currency FROM sales WHERE txid = $1",
+        txid
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// The most recent sales, of all geckos or of a single one.
+pub async fn recent(
+    pool: &PgPool,
+    gecko_number: Option<i64>,
+    limit: i64,
+) -> Result<Vec<SaleRecord>, sqlx::Error> {
+    match gecko_number {
+        Some(n) => {
+            sqlx::query_as!(
+                SaleRecord,
+                "SELECT gecko_number, identity, price, currency, buyer_discord_user_id, sold_at FROM sales WHERE gecko_number = $1 ORDER BY sold_at DESC LIMIT $2",
+                n,
+                limit
+            )
+            .fetch_all(pool)
+            .await
+        }
+        None => {
+            sqlx::query_as!(
+                SaleRecord,
+                "SELECT gecko_number, identity, price, currency, buyer_discord_user_id, sold_at FROM sales ORDER BY sold_at DESC LIMIT $1",
+                limit
+            )
+            .fetch_all(pool)
+            .await
+        }
+    }
+}
+
+pub async fn stats(pool: &PgPool) -> Result<MarketStats, sqlx::Error> {
+    let book = sqlx::query!(
+        "SELECT (SELECT MIN(price) FROM listings WHERE status = 'open') AS floor_price, (SELECT COUNT(*) FROM listings WHERE status = 'open') AS listed, (SELECT MAX(price) FROM bids WHERE status = 'open') AS best_bid, (SELECT COUNT(*) FROM bids WHERE status = 'open') AS bids"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let volume = sqlx::query!(
+        "SELECT COUNT(*) AS sales, SUM(price)::bigint AS volume, SUM(price) FILTER (WHERE sold_at > now() - interval '1 day')::bigint AS volume_day, SUM(price) FILTER (WHERE sold_at > now() - interval '7 days')::bigint AS volume_week FROM sales"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(MarketStats {
+        floor: book.floor_price.map(|price| price as u64),
+        listed: book.listed.unwrap_or(0),
+        best_bid: book.best_bid.map(|price| price as u64),
+        bids: book.bids.unwrap_or(0),
+        sales: volume.sales.unwrap_or(0),
+        volume: volume.volume.unwrap_or(0) as u64,
+        volume_day: volume.volume_day.unwrap_or(0) as u64,
+        volume_week: volume.volume_week.unwrap_or(0) as u64,
+        last_sale: recent(pool, None, 1).await?.into_iter().next(),
+    })
+}
+
+/// The floor of every value of a trait, cheapest first. Only geckos whose traits are known are
+/// counted, see `ensure_traits`.
+pub async fn trait_floors(pool: &PgPool, trait_type: &str) -> Result<Vec<TraitFloor>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT t.value, MIN(l.price) AS floor_price, COUNT(*) AS listed FROM listings l JOIN gecko_traits t ON t.gecko_number = l.gecko_number WHERE l.status = 'open' AND t.trait_type = $1 GROUP BY t.value ORDER BY floor_price",
+        trait_type
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(TraitFloor {
+                value: row.value,
+                floor: row.floor_price? as u64,
+                listed: row.listed.unwrap_or(0),
+            })
+        })
+        .collect())
+}
+
+/// The rarity rank of every gecko whose traits are known, 1 being the rarest. The rarity of a gecko
+/// is the product of the frequencies of its trait values.
+pub async fn ranks(pool: &PgPool) -> Result<HashMap<i64, i64>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "WITH frequencies AS (SELECT trait_type, value, COUNT(*)::float8 / (SELECT COUNT(DISTINCT gecko_number) FROM gecko_traits) AS frequency FROM gecko_traits GROUP BY trait_type, value), scores AS (SELECT t.gecko_number, SUM(ln(f.frequency)) AS score FROM gecko_traits t JOIN frequencies f ON f.trait_type = t.trait_type AND f.value = t.value GROUP BY t.gecko_number) SELECT gecko_number, RANK() OVER (ORDER BY score) AS rank FROM scores"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| Some((row.gecko_number, row.rank?)))
+        .collect())
+}
+
+/// How common each trait value of a gecko is among the geckos whose traits are known, by trait type.
+pub async fn trait_frequencies(
+    pool: &PgPool,
+    gecko_number: i64,
+) -> Result<HashMap<String, f64>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT t.trait_type, t.value, COUNT(*)::float8 / (SELECT COUNT(DISTINCT gecko_number) FROM gecko_traits) AS frequency FROM gecko_traits t WHERE (t.trait_type, t.value) IN (SELECT trait_type, value FROM gecko_traits WHERE gecko_number = $1) GROUP BY t.trait_type, t.value",
+        gecko_number
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| Some((row.trait_type, row.frequency?)))
+        .collect())
+}
+
+/// Makes sure the traits of a gecko are stored, by storing its metadata in the cache, see
+/// `metadata_cache::get`. `metadata_tx` is the arweave transaction of the metadata, see
+/// `metadata_txid`.
+pub async fn ensure_traits(
+    pool: &PgPool,
+    gecko_number: i64,
+    identity: &str,
+    metadata_tx: Option<&str>,
+) -> Result<(), MetadataError> {
+    let metadata_tx = match metadata_tx {
+        Some(metadata_tx) => metadata_tx,
+        None => {
+            debug!("gecko {} has no metadata in its contentmap", gecko_number);
+            return Ok(());
+        }
+    };
+
+    match metadata_cache::get(pool, gecko_number, identity, metadata_tx).await {
+        Ok(_) => Ok(()),
+        Err(MetadataError::Arweave(e)) => {
+            // not confirmed yet, try again next time
+            debug!("no metadata for gecko {}: {:?}", gecko_number, e);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,157 @@
+// Finds geckos by their traits, rarity rank, custody and whether they are listed. Everything comes
+// from the indexed tables (`gecko_ownership`, `gecko_traits` and `listings`), Arweave is not
+// queried, so geckos whose traits are not stored yet (see `sales::ensure_traits`) are only found
+// without a trait filter.
+use sqlx::PgPool;
+use std::{cmp::Ordering, str::FromStr};
+
+use crate::bot::{custody::Custody, sales};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchFilter {
+    // pairs of trait type and value, all of which a gecko has to have
+    pub traits: Vec<(String, String)>,
+    pub min_rank: Option<i64>,
+    pub max_rank: Option<i64>,
+    pub custody: Option<Custody>,
+    pub listed: Option<bool>,
+}
+
+/// A gecko that matched, with a price if it is listed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    pub gecko_number: i64,
+    pub rank: Option<i64>,
+    pub custody: Option<Custody>,
+    pub price: Option<u64>,
+}
+
+impl SearchFilter {
+    /// Parses trait filters like `base:tokay background:4c8358`. Types and values are compared
+    /// case insensitively.
+    pub fn parse_traits(s: &str) -> Result<Vec<(String, String)>, String> {
+        s.split_whitespace()
+            .map(|pair| match pair.split_once(':') {
+                Some((trait_type, value)) if !trait_type.is_empty() && !value.is_empty() => {
+                    Ok((trait_type.to_lowercase(), value.to_lowercase()))
+                }
+                _ => Err(format!("`{}` is not a `trait:value` pair", pair)),
+            })
+            .collect()
+    }
+
+    // The trait filter is applied by the database already.
+    fn matches(&self, found: &Found) -> bool {
+        let rank_matches = match found.rank {
+            Some(rank) => {
+                self.min_rank.map(|min| rank >= min).unwrap_or(true)
+                    && self.max_rank.map(|max| rank <= max).unwrap_or(true)
+            }
+            None => self.min_rank.is_none() && self.max_rank.is_none(),
+        };
+        let custody_matches = self
+            .custody
+            .map(|custody| found.custody == Some(custody))
+            .unwrap_or(true);
+        let listed_matches = self
+            .listed
+            .map(|listed| found.price.is_some() == listed)
+            .unwrap_or(true);
+
+        rank_matches && custody_matches && listed_matches
+    }
+}
+
+/// The geckos that match the filter, rarest first. Geckos without a rank come last.
+pub async fn search(pool: &PgPool, filter: &SearchFilter) -> Result<Vec<Found>, sqlx::Error> {
+    let (trait_types, values): (Vec<String>, Vec<String>) = filter.traits.iter().cloned().unzip();
+
+    let rows = sqlx::query!(
+        "SELECT o.gecko_number, c.custody, (SELECT MIN(l.price) FROM listings l WHERE l.gecko_number = o.gecko_number AND l.status = 'open') AS price FROM gecko_ownership o LEFT JOIN gecko_custody c ON c.gecko_number = o.gecko_number WHERE (SELECT COUNT(*) FROM gecko_traits t JOIN UNNEST($1::text[], $2::text[]) AS f(trait_type, value) ON lower(t.trait_type) = f.trait_type AND lower(t.value) = f.value WHERE t.gecko_number = o.gecko_number) = cardinality($1::text[]) ORDER BY o.gecko_number",
+        &trait_types,
+        &values
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let ranks = sales::ranks(pool).await?;
+    let mut found = rows
+        .into_iter()
+        .map(|row| Found {
+            gecko_number: row.gecko_number,
+            rank: ranks.get(&row.gecko_number).copied(),
+            custody: row
+                .custody
+                .and_then(|custody| Custody::from_str(&custody).ok()),
+            price: row.price.map(|price| price as u64),
+        })
+        .filter(|found| filter.matches(found))
+        .collect::<Vec<_>>();
+
+    found.sort_by(|a, b| match (a.rank, b.rank) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Found, SearchFilter};
+    use crate::bot::custody::Custody;
+
+    #[test]
+    fn trait_filters_are_parsed() {
+        assert_eq!(
+            SearchFilter::parse_traits("base:Tokay  background:4c8358"),
+            Ok(vec![
+                ("base".to_string(), "tokay".to_string()),
+                ("background".to_string(), "4c8358".to_string())
+            ])
+        );
+        assert_eq!(SearchFilter::parse_traits(""), Ok(vec![]));
+        assert!(SearchFilter::parse_traits("base").is_err());
+        assert!(SearchFilter::parse_traits("base:").is_err());
+    }
+
+    #[test]
+    fn rank_custody_and_listing_filters() {
+        let found = Found {
+            gecko_number: 7,
+            rank: Some(12),
+            custody: Some(Custody::Bot),
+            price: Some(100),
+        };
+        let unranked = Found {
+            rank: None,
+            ..found.clone()
+        };
+
+        assert!(SearchFilter::default().matches(&found));
+        assert!(SearchFilter::default().matches(&unranked));
+
+        let top_ten = SearchFilter {
+            max_rank: Some(10),
+            ..Default::default()
+        };
+        assert!(!top_ten.matches(&found));
+        assert!(!top_ten.matches(&unranked));
+
+        let listed_with_bot = SearchFilter {
+            min_rank: Some(5),
+            custody: Some(Custody::Bot),
+            listed: Some(true),
+            ..Default::default()
+        };
+        assert!(listed_with_bot.matches(&found));
+
+        let unlisted = SearchFilter {
+            listed: Some(false),
+            ..Default::default()
+        };
+        assert!(!unlisted.matches(&found));
+    }
+}
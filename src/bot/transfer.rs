@@ -0,0 +1,318 @@
+// Transfers of geckos that were initiated through the bot. This is the Verus counterpart of the
+// ERC-721 `Transfer(from, to, tokenId)` event: a transfer is recorded as pending when the identity
+// update is sent and `watch` confirms it once the update is mined.
+use serenity::prelude::Context;
+use sqlx::PgPool;
+use std::{fmt, str::FromStr, time::Duration};
+use tracing::{debug, error, info, instrument};
+use vrsc_rpc::{bitcoin::Txid, Auth, Client, RpcApi};
+
+use crate::{
+    bot::{
+        commands::event_bus,
+        custody::{self, Custody, GeckoCustody},
+        market, sales,
+        utils::dm,
+    },
+    configuration::Settings,
+    lifecycle::NftEvent,
+};
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferKind {
+    Gift,
+    Withdraw,
+    Sale,
+}
+
+impl TransferKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferKind::Gift => "gift",
+            TransferKind::Withdraw => "withdraw",
+            TransferKind::Sale => "sale",
+        }
+    }
+}
+
+impl fmt::Display for TransferKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for TransferKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gift" => Ok(TransferKind::Gift),
+            "withdraw" => Ok(TransferKind::Withdraw),
+            "sale" => Ok(TransferKind::Sale),
+            other => Err(format!("{} is not a valid transfer kind", other)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Transfer {
+    pub gecko_number: i64,
+    pub identity: String,
+    pub kind: TransferKind,
+    pub from_discord_user_id: Option<u64>,
+    pub to_discord_user_id: Option<u64>,
+    pub from_address: Option<String>,
+    pub to_address: String,
+    pub txid: String,
+}
+
+/// Stores a transfer as pending and returns its id.
+pub async fn record(pool: &PgPool, transfer: &Transfer) -> Result<i64, sqlx::Error> {
+    let record = sqlx::query!(
+        "INSERT INTO transfers (gecko_number, identity, kind, from_discord_user_id, to_discord_user_id, from_address, to_address, txid, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'pending') RETURNING id",
+        transfer.gecko_number,
+        transfer.identity,
+        transfer.kind.as_str(),
+        transfer.from_discord_user_id.map(|id| id as i64),
+        transfer.to_discord_user_id.map(|id| id as i64),
+        transfer.from_address,
+        transfer.to_address,
+        transfer.txid
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(record.id)
+}
+
+async fn set_status(pool: &PgPool, id: i64, status: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE transfers SET status = $2, confirmed_at = now() WHERE id = $1",
+        id,
+        status
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Periodically checks the pending transfers. Since the pending state lives in the database,
+/// transfers that were sent before a restart are picked up again.
+pub async fn watch(ctx: Context, app_config: Settings, pool: PgPool) {
+    loop {
+        if let Err(e) = confirm_pending(&ctx, &app_config, &pool).await {
+            error!("checking pending transfers failed: {:?}", e);
+        }
+
+        tokio::time::sleep(WATCH_INTERVAL).await;
+    }
+}
+
+#[instrument(skip_all)]
+async fn confirm_pending(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = match app_config.application.testnet {
+        true => Client::chain("vrsctest", Auth::ConfigFile, None),
+        false => Client::chain("VRSC", Auth::ConfigFile, None),
+    }?;
+
+    let pending = sqlx::query!(
+        "SELECT id, gecko_number, identity, kind, from_discord_user_id, to_discord_user_id, from_address, to_address, txid FROM transfers WHERE status = 'pending'"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in pending {
+        // a row that can not be read would otherwise hold up every transfer after it
+        let (txid, kind) = match (Txid::from_str(&row.txid), row.kind.parse::<TransferKind>()) {
+            (Ok(txid), Ok(kind)) => (txid, kind),
+            (txid, kind) => {
+                error!(
+                    "invalid pending transfer {} (txid: {:?}, kind: {:?}), marking it failed",
+                    row.id,
+                    txid.err(),
+                    kind.err()
+                );
+                set_status(pool, row.id, "failed").await?;
+                continue;
+            }
+        };
+        let confirmations = match client.get_transaction(&txid, None) {
+            Ok(tx) => tx.confirmations,
+            Err(e) => {
+                debug!("could not get transaction {}: {:?}", &row.txid, e);
+                continue;
+            }
+        };
+
+        let transfer = Transfer {
+            gecko_number: row.gecko_number,
+            identity: row.identity,
+            kind,
+            from_discord_user_id: row.from_discord_user_id.map(|id| id as u64),
+            to_discord_user_id: row.to_discord_user_id.map(|id| id as u64),
+            from_address: row.from_address,
+            to_address: row.to_address,
+            txid: row.txid,
+        };
+
+        if confirmations > 0 {
+            set_status(pool, row.id, "confirmed").await?;
+            on_confirmed(ctx, app_config, pool, &transfer).await?;
+        } else if confirmations < 0 {
+            // the update conflicted with another update of the same identity
+            set_status(pool, row.id, "failed").await?;
+            error!("transfer {} failed: {:?}", row.id, &transfer);
+
+            if transfer.kind == TransferKind::Sale {
+                sales::remove(pool, &transfer.txid).await?;
+                market::reopen(ctx, app_config, pool, &transfer.txid).await?;
+            }
+
+            if let Some(user_id) = transfer.from_discord_user_id {
+                dm::send(
+                    ctx,
+                    user_id,
+                    format!(
+                        "The {} of `{}` did not go through, please try again.",
+                        transfer.kind, transfer.identity
+                    ),
+                )
+                .await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn on_confirmed(
+    ctx: &Context,
+    app_config: &Settings,
+    pool: &PgPool,
+    transfer: &Transfer,
+) -> Result<(), sqlx::Error> {
+    info!(
+        "Transfer({:?}, {}, {})",
+        transfer.from_discord_user_id, &transfer.to_address, &transfer.identity
+    );
+
+    let custody = match transfer.to_discord_user_id {
+        Some(_) => Custody::Bot,
+        None => Custody::Withdrawn,
+    };
+
+    custody::store(
+        pool,
+        &GeckoCustody {
+            gecko_number: transfer.gecko_number,
+            identity: transfer.identity.clone(),
+            discord_user_id: transfer.to_discord_user_id,
+            custody,
+        },
+    )
+    .await?;
+
+    match transfer.kind {
+        TransferKind::Gift => {
+            if let Some(from) = transfer.from_discord_user_id {
+                let to = transfer
+                    .to_discord_user_id
+                    .map(|id| format!("<@{}>", id))
+                    .unwrap_or_else(|| format!("`{}`", transfer.to_address));
+                dm::send(
+                    ctx,
+                    from,
+                    format!("`{}` has been gifted to {}.", transfer.identity, to),
+                )
+                .await;
+            }
+
+            if let Some(to) = transfer.to_discord_user_id {
+                let from = transfer
+                    .from_discord_user_id
+                    .map(|id| format!("<@{}>", id))
+                    .unwrap_or_else(|| String::from("someone"));
+                dm::send(
+                    ctx,
+                    to,
+                    format!(
+                        "You received `{}` from {}! Use `/gecko {}` to have a look.",
+                        transfer.identity, from, transfer.gecko_number
+                    ),
+                )
+                .await;
+            }
+        }
+        TransferKind::Sale => {
+            if let Some(from) = transfer.from_discord_user_id {
+                dm::send(ctx, from, format!("`{}` has been sold!", transfer.identity)).await;
+            }
+
+            if let Some(to) = transfer.to_discord_user_id {
+                dm::send(
+                    ctx,
+                    to,
+                    format!(
+                        "You bought `{}`! Use `/gecko {}` to have a look.",
+                        transfer.identity, transfer.gecko_number
+                    ),
+                )
+                .await;
+            }
+
+            // sales that were made through the bot are announced in the market channel
+            if let Some(offer) = market::get_filled_by(pool, &transfer.txid).await? {
+                market::announce_sale(ctx, app_config, &offer).await;
+            }
+
+            if let Some(sale) = sales::get_by_txid(pool, &transfer.txid).await? {
+                event_bus(ctx).await.publish(NftEvent::Sold {
+                    gecko_number: transfer.gecko_number,
+                    identity: transfer.identity.clone(),
+                    price: sale.price as u64,
+                    currency: sale.currency,
+                    offer_txid: sale.offer_txid,
+                    txid: Some(transfer.txid.clone()),
+                });
+            }
+        }
+        TransferKind::Withdraw => {
+            if let Some(from) = transfer.from_discord_user_id {
+                dm::send(
+                    ctx,
+                    from,
+                    format!(
+                        "`{}` has been withdrawn to `{}`.",
+                        transfer.identity, transfer.to_address
+                    ),
+                )
+                .await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TransferKind;
+
+    #[test]
+    fn transfer_kind_roundtrip() {
+        for kind in [
+            TransferKind::Gift,
+            TransferKind::Withdraw,
+            TransferKind::Sale,
+        ] {
+            assert_eq!(kind.as_str().parse::<TransferKind>(), Ok(kind));
+        }
+    }
+}
@@ -12,3 +12,50 @@ pub async fn obtain_postgres_pool(
 
     Ok(pool)
 }
+
+// Returns the bot-generated address that is mapped to a Discord user, if the user is registered.
+pub async fn get_user_address(
+    pool: &PgPool,
+    discord_user_id: u64,
+) -> Result<Option<String>, sqlx::Error> {
+    let record = sqlx::query!(
+        "SELECT vrsc_address FROM user_register WHERE discord_user_id = $1",
+        discord_user_id as i64
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.and_then(|r| r.vrsc_address))
+}
+
+// Maps a bot-generated address to a Discord user, replacing the address the user had before.
+pub async fn register_user_address(
+    pool: &PgPool,
+    discord_user_id: u64,
+    vrsc_address: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO user_register (discord_user_id, vrsc_address) VALUES ($1, $2) ON CONFLICT (discord_user_id) DO UPDATE SET vrsc_address = EXCLUDED.vrsc_address",
+        discord_user_id as i64,
+        vrsc_address
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Returns the Discord user that a bot-generated address is mapped to.
+pub async fn get_user_by_address(
+    pool: &PgPool,
+    vrsc_address: &str,
+) -> Result<Option<u64>, sqlx::Error> {
+    let record = sqlx::query!(
+        "SELECT discord_user_id FROM user_register WHERE vrsc_address = $1",
+        vrsc_address
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|r| r.discord_user_id as u64))
+}
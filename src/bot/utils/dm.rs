@@ -0,0 +1,21 @@
+use serenity::{model::id::UserId, prelude::Context};
+use tracing::error;
+
+// Sends a direct message to a user. DMs are best effort, users can have them turned off.
+pub async fn send<S: ToString>(ctx: &Context, user_id: u64, content: S) {
+    if let Err(e) = try_send(ctx, user_id, content).await {
+        error!("Sending DM to user error: {:?}", e);
+    }
+}
+
+// For callers that have another way to reach the user when the DM does not arrive.
+pub async fn try_send<S: ToString>(
+    ctx: &Context,
+    user_id: u64,
+    content: S,
+) -> Result<(), serenity::Error> {
+    let dm = UserId(user_id).create_dm_channel(ctx).await?;
+    dm.say(&ctx.http, content.to_string()).await?;
+
+    Ok(())
+}
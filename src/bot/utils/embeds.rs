@@ -1,32 +1,219 @@
+// The embed of a single gecko, shared by the announcements, `/gecko` and `/list`. Callers put what
+// they know about the gecko in a `GeckoView` and `render` lays it out; whatever is not known is left
+// out, and uploads that are not confirmed yet show as pending.
 use serenity::builder::CreateEmbed;
+use std::collections::HashMap;
 
-use crate::nft::VerusNFT;
+use crate::nft::{metadata::NFTMetadata, VerusNFT};
 
-pub fn from_verusnft(e: &mut CreateEmbed, verus_nft: VerusNFT) -> &mut CreateEmbed {
-    // Todo: let VerusNFT have a metadata variable.
-    e.title(format!(
-        "Introducing TEST Goofy Geckos TEST #{}",
-        verus_nft.sequence
-    ))
-    .description(format!("**Rarity:** {}\n", verus_nft.rarity))
-    .field(
-        "Transaction",
-        format!(
-            "[view](https://v2.viewblock.io/arweave/tx/{})",
-            verus_nft.uploaded_image_tx_hash.as_ref().unwrap()
-        ),
+const ARWEAVE_EXPLORER: &str = "https://v2.viewblock.io/arweave/tx";
+const ARWEAVE_GATEWAY: &str = "https://arweave.net";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeckoView {
+    pub title: String,
+    pub identity: String,
+    // the product of the weights of its traits, the odds of drawing it when they add up to 1
+    pub rarity: Option<f64>,
+    pub rank: Option<i64>,
+    pub traits: Vec<TraitView>,
+    pub owner: Option<String>,
+    pub custody: Option<String>,
+    pub vault: Option<String>,
+    pub image_tx: Option<String>,
+    pub metadata_tx: Option<String>,
+    // whether the uploads are confirmed on Arweave
+    pub confirmed: bool,
+    pub footer: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraitView {
+    pub trait_type: String,
+    pub value: String,
+    // the share of the geckos with this value
+    pub frequency: Option<f64>,
+}
+
+impl GeckoView {
+    pub(crate) fn from_metadata(
+        metadata: &NFTMetadata,
+        identity: &str,
+        metadata_tx: &str,
+        confirmed: bool,
+    ) -> Self {
+        GeckoView {
+            title: metadata.name.clone(),
+            identity: identity.to_string(),
+            rarity: Some(metadata.rarity as f64),
+            traits: traits(metadata),
+            image_tx: Some(metadata.image.clone()),
+            metadata_tx: Some(metadata_tx.to_string()),
+            confirmed,
+            ..Default::default()
+        }
+    }
+
+    /// A gecko whose metadata can not be read until its upload is confirmed on Arweave.
+    pub(crate) fn pending(title: &str, identity: &str, metadata_tx: &str) -> Self {
+        GeckoView {
+            title: title.to_string(),
+            identity: identity.to_string(),
+            metadata_tx: Some(metadata_tx.to_string()),
+            confirmed: false,
+            ..Default::default()
+        }
+    }
+
+    /// A gecko that was just minted. Its traits are read from the metadata the pipeline generated.
+    pub(crate) fn from_verusnft(verus_nft: &VerusNFT, title: &str) -> Self {
+        let metadata = verus_nft
+            .generated_metadata_path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|metadata| serde_json::from_str::<NFTMetadata>(&metadata).ok());
+
+        GeckoView {
+            title: title.to_string(),
+            identity: format!("{}.{}@", verus_nft.sequence, &verus_nft.edition),
+            rarity: Some(verus_nft.rarity).filter(|rarity| *rarity > 0.0),
+            traits: metadata
+                .map(|metadata| traits(&metadata))
+                .unwrap_or_default(),
+            owner: Some(format!("<@{}>", verus_nft.user_id)),
+            image_tx: verus_nft.uploaded_image_tx_hash.clone(),
+            metadata_tx: verus_nft.uploaded_metadata_tx_hash.clone(),
+            // the pipeline waits for the uploads before it announces, unless it only simulated them
+            confirmed: !verus_nft.dry_run,
+            ..Default::default()
+        }
+    }
+
+    /// Fills in the frequencies of the traits, by trait type.
+    pub fn with_frequencies(mut self, frequencies: &HashMap<String, f64>) -> Self {
+        for t in &mut self.traits {
+            t.frequency = frequencies.get(&t.trait_type).copied();
+        }
+
+        self
+    }
+}
+
+fn traits(metadata: &NFTMetadata) -> Vec<TraitView> {
+    metadata
+        .attributes
+        .iter()
+        .map(|t| TraitView {
+            trait_type: t.trait_type.clone(),
+            value: t.value.clone(),
+            frequency: None,
+        })
+        .collect()
+}
+
+pub fn render<'a>(e: &'a mut CreateEmbed, view: &GeckoView) -> &'a mut CreateEmbed {
+    let mut description = vec![format!("**Rarity:** {}", rarity(view.rarity))];
+    if let Some(rank) = view.rank {
+        description.push(format!("**Rank:** {}", rank));
+    }
+
+    e.title(&view.title)
+        .description(description.join("\n"))
+        .field("Identity", format!("`{}`", &view.identity), true);
+
+    // traits starting with `_` only select other traits and are not part of the gecko
+    for t in view
+        .traits
+        .iter()
+        .filter(|t| !t.trait_type.starts_with('_'))
+    {
+        let value = match t.frequency {
+            Some(frequency) => format!("{} ({})", &t.value, percentage(frequency)),
+            None => t.value.clone(),
+        };
+        e.field(&t.trait_type, value, true);
+    }
+
+    for (name, value) in [
+        ("Owner", &view.owner),
+        ("Custody", &view.custody),
+        ("Vault", &view.vault),
+    ] {
+        if let Some(value) = value {
+            e.field(name, value, true);
+        }
+    }
+
+    e.field(
+        "Metadata",
+        link(view.metadata_tx.as_deref(), view.confirmed),
         true,
     )
     .field(
-        "Metadata",
-        format!(
-            "[view](https://v2.viewblock.io/arweave/tx/{})",
-            verus_nft.uploaded_metadata_tx_hash.as_ref().unwrap()
-        ),
+        "Image",
+        link(view.image_tx.as_deref(), view.confirmed),
         true,
-    )
-    .image(format!(
-        "https://arweave.net/{}",
-        &verus_nft.uploaded_image_tx_hash.as_ref().unwrap()
-    ))
+    );
+
+    if let Some(image_tx) = &view.image_tx {
+        e.image(format!("{}/{}", ARWEAVE_GATEWAY, image_tx));
+    }
+    if let Some(footer) = &view.footer {
+        e.footer(|f| f.text(footer));
+    }
+
+    e
+}
+
+fn rarity(rarity: Option<f64>) -> String {
+    match rarity {
+        Some(rarity) if rarity > 0.0 && rarity < 1.0 => {
+            format!("1 in {}", (1.0 / rarity).round())
+        }
+        Some(rarity) if rarity >= 1.0 => format!("{:.2}", rarity),
+        _ => String::from("_unknown_"),
+    }
+}
+
+fn percentage(frequency: f64) -> String {
+    match frequency * 100.0 {
+        p if p < 0.1 => String::from("<0.1%"),
+        p => format!("{:.1}%", p),
+    }
+}
+
+fn link(tx: Option<&str>, confirmed: bool) -> String {
+    match (tx, confirmed) {
+        (Some(tx), true) => format!("[view]({}/{})", ARWEAVE_EXPLORER, tx),
+        (Some(tx), false) => format!("[pending]({}/{})", ARWEAVE_EXPLORER, tx),
+        (None, _) => String::from("_pending_"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{link, percentage, rarity};
+
+    #[test]
+    fn values_are_readable() {
+        assert_eq!(rarity(Some(0.0012)), "1 in 833");
+        assert_eq!(rarity(Some(2.5)), "2.50");
+        assert_eq!(rarity(Some(0.0)), "_unknown_");
+        assert_eq!(rarity(None), "_unknown_");
+        assert_eq!(percentage(0.125), "12.5%");
+        assert_eq!(percentage(0.0004), "<0.1%");
+    }
+
+    #[test]
+    fn unconfirmed_uploads_are_pending() {
+        assert_eq!(
+            link(Some("tx"), true),
+            "[view](https://v2.viewblock.io/arweave/tx/tx)"
+        );
+        assert_eq!(
+            link(Some("tx"), false),
+            "[pending](https://v2.viewblock.io/arweave/tx/tx)"
+        );
+        assert_eq!(link(None, true), "_pending_");
+    }
 }
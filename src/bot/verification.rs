@@ -0,0 +1,236 @@
+// Login with your gecko: proves that a Discord account or a web session belongs to whoever controls
+// a gecko. The holder asks for a challenge, signs its message with `signmessage` using the gecko
+// identity or one of its primary addresses, and the signature is checked with `verifymessage`.
+// A proof is valid for `PROOF_VALIDITY_DAYS`, after which the holder has to verify again.
+use serde_json::json;
+use sqlx::{
+    types::chrono::{DateTime, Duration, Utc},
+    PgPool,
+};
+use tracing::info;
+use uuid::Uuid;
+use vrsc_rpc::{Client, RpcApi};
+
+use crate::{
+    bot::{commands::gecko_identity_name, indexer},
+    configuration::Settings,
+};
+
+// how long a challenge can be signed
+const CHALLENGE_VALIDITY_MINUTES: i64 = 15;
+const PROOF_VALIDITY_DAYS: i64 = 30;
+
+/// Who is proving ownership.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Holder {
+    Discord(u64),
+    // an opaque id of a web session, chosen by the frontend
+    Session(String),
+}
+
+impl Holder {
+    fn columns(&self) -> (Option<i64>, Option<&str>) {
+        match self {
+            Holder::Discord(id) => (Some(*id as i64), None),
+            Holder::Session(session) => (None, Some(session.as_str())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Challenge {
+    pub nonce: String,
+    pub gecko_number: i64,
+    pub identity: String,
+    pub message: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct Proof {
+    pub gecko_number: i64,
+    pub identity: String,
+    pub signer: String,
+    pub verified_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Display)]
+pub enum VerificationError {
+    #[display(fmt = "gecko #{} is not known", _0)]
+    UnknownGecko(i64),
+    #[display(fmt = "there is no open challenge, ask for a new one")]
+    NoChallenge,
+    #[display(fmt = "`{}` is not the identity or a primary address of the gecko", _0)]
+    NotAnOwner(String),
+    #[display(fmt = "the signature does not match the message")]
+    InvalidSignature,
+    #[display(fmt = "something went wrong, try again later")]
+    Database(sqlx::Error),
+    #[display(fmt = "something went wrong, try again later")]
+    Verus(vrsc_rpc::Error),
+}
+
+impl From<sqlx::Error> for VerificationError {
+    fn from(e: sqlx::Error) -> Self {
+        VerificationError::Database(e)
+    }
+}
+
+impl From<vrsc_rpc::Error> for VerificationError {
+    fn from(e: vrsc_rpc::Error) -> Self {
+        VerificationError::Verus(e)
+    }
+}
+
+// What is signed. The nonce makes every message unique, so a signature can not be replayed.
+fn challenge_message(identity: &str, nonce: &str) -> String {
+    format!("I control {} - {}", identity, nonce)
+}
+
+/// Starts a verification. A previous open challenge of the holder for the same gecko is replaced.
+pub async fn challenge(
+    pool: &PgPool,
+    app_config: &Settings,
+    holder: &Holder,
+    gecko_number: i64,
+) -> Result<Challenge, VerificationError> {
+    if indexer::get(pool, gecko_number).await?.is_none() {
+        return Err(VerificationError::UnknownGecko(gecko_number));
+    }
+
+    let identity = gecko_identity_name(app_config, gecko_number);
+    let nonce = Uuid::new_v4().to_string();
+    let expires_at = Utc::now() + Duration::minutes(CHALLENGE_VALIDITY_MINUTES);
+    let (discord_user_id, session_id) = holder.columns();
+
+    // expired challenges of anyone go as well
+    sqlx::query!(
+        "DELETE FROM verification_challenges WHERE expires_at < now() OR (gecko_number = $1 AND (discord_user_id = $2 OR session_id = $3))",
+        gecko_number,
+        discord_user_id,
+        session_id
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO verification_challenges (nonce, gecko_number, discord_user_id, session_id, expires_at) VALUES ($1, $2, $3, $4, $5)",
+        nonce,
+        gecko_number,
+        discord_user_id,
+        session_id,
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(Challenge {
+        message: challenge_message(&identity, &nonce),
+        nonce,
+        gecko_number,
+        identity,
+        expires_at,
+    })
+}
+
+/// Checks the signature of the open challenge of the holder. `signer` is the identity of the
+/// gecko or one of its primary addresses, whichever was used with `signmessage`.
+pub async fn verify(
+    pool: &PgPool,
+    app_config: &Settings,
+    client: &Client,
+    holder: &Holder,
+    gecko_number: i64,
+    signer: &str,
+    signature: &str,
+) -> Result<Proof, VerificationError> {
+    let (discord_user_id, session_id) = holder.columns();
+    let challenge = sqlx::query!(
+        "SELECT nonce FROM verification_challenges WHERE gecko_number = $1 AND (discord_user_id = $2 OR session_id = $3) AND expires_at > now()",
+        gecko_number,
+        discord_user_id,
+        session_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(VerificationError::NoChallenge)?;
+
+    let ownership = indexer::get(pool, gecko_number)
+        .await?
+        .ok_or(VerificationError::UnknownGecko(gecko_number))?;
+    let identity = gecko_identity_name(app_config, gecko_number);
+
+    let is_owner = signer == identity
+        || signer == ownership.identity_address
+        || ownership
+            .primary_addresses
+            .iter()
+            .any(|address| address == signer);
+    if !is_owner {
+        return Err(VerificationError::NotAnOwner(signer.to_string()));
+    }
+
+    let valid: bool = client.call(
+        "verifymessage",
+        &[
+            json!(signer),
+            json!(signature),
+            json!(challenge_message(&identity, &challenge.nonce)),
+        ],
+    )?;
+    if !valid {
+        return Err(VerificationError::InvalidSignature);
+    }
+
+    // a challenge is good for one proof only
+    sqlx::query!(
+        "DELETE FROM verification_challenges WHERE nonce = $1",
+        challenge.nonce
+    )
+    .execute(pool)
+    .await?;
+
+    let verified_at = Utc::now();
+    let expires_at = verified_at + Duration::days(PROOF_VALIDITY_DAYS);
+    sqlx::query!(
+        "INSERT INTO ownership_proofs (gecko_number, identity, discord_user_id, session_id, signer, signature, verified_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        gecko_number,
+        identity,
+        discord_user_id,
+        session_id,
+        signer,
+        signature,
+        verified_at,
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    info!(
+        "{:?} proved to control {} with {}",
+        holder, &identity, signer
+    );
+
+    Ok(Proof {
+        gecko_number,
+        identity,
+        signer: signer.to_string(),
+        verified_at,
+        expires_at,
+    })
+}
+
+/// The proofs of a holder that did not expire, newest first.
+pub async fn proofs(pool: &PgPool, holder: &Holder) -> Result<Vec<Proof>, sqlx::Error> {
+    let (discord_user_id, session_id) = holder.columns();
+
+    sqlx::query_as!(
+        Proof,
+        "SELECT gecko_number, identity, signer, verified_at, expires_at FROM ownership_proofs WHERE (discord_user_id = $1 OR session_id = $2) AND expires_at > now() ORDER BY verified_at DESC",
+        discord_user_id,
+        session_id
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,302 @@
+// The subcommands of `verusnft`. Without one, or with `bot`, the Discord bot starts; the others run a
+// single operation with the same library code and exit, so operators can script them and debug one
+// step of the pipeline without Discord.
+use clap::{Parser, Subcommand};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use vrsc_rpc::{Auth, Client, RpcApi};
+
+use crate::{
+    api,
+    bot::{
+        metadata_cache, minting,
+        sales::{metadata_txid, METADATA_VDXF_KEY},
+        utils::database::obtain_postgres_pool,
+    },
+    configuration::Settings,
+    lifecycle::EventBus,
+    nft::{art, arweave, config, metadata},
+};
+
+type CliResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+#[derive(Parser)]
+#[clap(name = "verusnft", version, about = "Goofy Geckos on Verus")]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Runs the Discord bot, and the HTTP API when it is enabled
+    Bot,
+    /// Serves the HTTP API without the bot
+    Api,
+    /// Mints a gecko for a Discord user through the mint queue, without announcing it
+    Mint {
+        #[clap(long)]
+        user_id: u64,
+    },
+    /// Generates the metadata and the art of a gecko, without uploading them
+    Generate {
+        #[clap(long)]
+        user_id: u64,
+        /// The number of the gecko; the number the next mint gets by default
+        #[clap(long)]
+        sequence: Option<u64>,
+        /// Without the database, numbering from `sequence_start`
+        #[clap(long)]
+        offline: bool,
+    },
+    /// Uploads a file to Arweave and prints its transaction
+    Upload {
+        path: PathBuf,
+        #[clap(long, default_value = "image/png")]
+        content_type: String,
+    },
+    /// Prints the identity and the metadata of a gecko
+    Lookup { number: i64 },
+    /// Checks that the identity of a gecko points at metadata and an image that are on Arweave
+    Verify { number: i64 },
+    /// Writes the metadata of every gecko to a directory
+    Export {
+        /// metaplex, opensea or verus
+        #[clap(default_value = "opensea")]
+        format: String,
+        #[clap(default_value = "./export")]
+        directory: PathBuf,
+    },
+    /// Runs the database migrations
+    Migrate,
+    /// Fills the metadata cache for geckos that were minted before it existed
+    Backfill,
+    /// Checks the asset configuration and the layers of the art
+    ValidateAssets,
+}
+
+/// Runs a subcommand other than `bot`.
+pub async fn run(command: Command, app_config: Settings) -> CliResult {
+    match command {
+        Command::Bot => Err("the bot is started by main".into()),
+        Command::Api => api::serve(app_config.clone(), database(&app_config).await?).await,
+        Command::Mint { user_id } => mint(&app_config, user_id).await,
+        Command::Generate {
+            user_id,
+            sequence,
+            offline,
+        } => generate(&app_config, user_id, sequence, offline).await,
+        Command::Upload { path, content_type } => {
+            let tags = vec![("Content-Type", content_type.as_str())];
+            if app_config.application.dry_run {
+                println!("{}", arweave::simulate_upload(&path, &tags));
+                return Ok(());
+            }
+
+            let mut transaction = arweave::ArweaveTransaction::new(Path::new(
+                &app_config.application.ardrive_wallet_location,
+            ))
+            .await;
+            let tx = transaction
+                .upload(&path, tags)
+                .await
+                .map_err(|_| format!("could not upload {}", path.display()))?;
+            println!("{}", tx);
+
+            Ok(())
+        }
+        Command::Lookup { number } => lookup(&app_config, number).await,
+        Command::Verify { number } => verify(&app_config, number).await,
+        Command::Export { format, directory } => {
+            let pool = database(&app_config).await?;
+            api::export::export(&app_config, &pool, &format, &directory).await
+        }
+        Command::Migrate => {
+            database(&app_config).await?;
+            info!("the database is up to date");
+
+            Ok(())
+        }
+        Command::Backfill => {
+            let pool = database(&app_config).await?;
+            metadata_cache::backfill(&app_config, &pool).await?;
+
+            Ok(())
+        }
+        Command::ValidateAssets => {
+            let location = format!("{}/config.json", &app_config.application.assets_dir);
+            let asset_config = config::parse(&location)?;
+            let problems =
+                config::validate(&asset_config, Path::new(&app_config.application.assets_dir));
+            if problems.is_empty() {
+                println!("{} is valid", location);
+                return Ok(());
+            }
+
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            Err(format!("{} has {} problems", location, problems.len()).into())
+        }
+    }
+}
+
+/// A connection to the database, migrated to the latest schema.
+pub async fn database(app_config: &Settings) -> Result<PgPool, Box<dyn Error + Send + Sync>> {
+    let pool = obtain_postgres_pool(&app_config.database).await?;
+    sqlx::migrate!("./migrations").run(&pool).await?;
+
+    Ok(pool)
+}
+
+fn verus_client(app_config: &Settings) -> Result<Client, vrsc_rpc::Error> {
+    match app_config.application.testnet {
+        true => Client::chain("vrsctest", Auth::ConfigFile, None),
+        false => Client::chain("VRSC", Auth::ConfigFile, None),
+    }
+}
+
+// Events are published on a bus without subscribers, there is no Discord to deliver them to.
+async fn mint(app_config: &Settings, user_id: u64) -> CliResult {
+    let pool = database(app_config).await?;
+    let id = minting::enqueue(&pool, user_id).await?;
+    info!("minting for {} in job {}", user_id, id);
+
+    let verus_nft = minting::mint(app_config, &pool, &EventBus::default(), id).await?;
+    println!(
+        "{}.{}@ (metadata {}, image {})",
+        verus_nft.sequence,
+        &verus_nft.edition,
+        verus_nft
+            .uploaded_metadata_tx_hash
+            .as_deref()
+            .unwrap_or("-"),
+        verus_nft.uploaded_image_tx_hash.as_deref().unwrap_or("-")
+    );
+
+    Ok(())
+}
+
+async fn generate(
+    app_config: &Settings,
+    user_id: u64,
+    sequence: Option<u64>,
+    offline: bool,
+) -> CliResult {
+    let application = &app_config.application;
+    let sequence = match (sequence, offline) {
+        (Some(sequence), _) => sequence,
+        (None, true) => application.sequence_start,
+        (None, false) => {
+            let pool = database(app_config).await?;
+            let next = sqlx::query!(
+                "SELECT last_value + CASE WHEN is_called THEN 1 ELSE 0 END AS next FROM goofygeckoserial"
+            )
+            .fetch_one(&pool)
+            .await?
+            .next
+            .unwrap_or(1);
+            next as u64 + application.sequence_start
+        }
+    };
+
+    let asset_config_location = format!("{}/config.json", &application.assets_dir);
+    metadata::generate(user_id, sequence, Path::new(&asset_config_location)).await;
+    let image = art::generate(
+        user_id,
+        Path::new(&application.assets_dir),
+        Path::new(&application.output_dir),
+    )
+    .await
+    .map_err(|_| "could not generate the art")?;
+
+    println!(
+        "#{}: {}/{}.json, {}",
+        sequence,
+        &application.output_dir,
+        user_id,
+        image.display()
+    );
+
+    Ok(())
+}
+
+// The metadata transaction in the contentmap of the identity of a gecko.
+fn identity_metadata_tx(
+    app_config: &Settings,
+    client: &Client,
+    number: i64,
+) -> Result<(String, String), Box<dyn Error + Send + Sync>> {
+    let name = format!("{}.{}@", number, &app_config.application.series);
+    let identity = client.get_identity(&name)?;
+    let metadata_tx = identity
+        .identity
+        .contentmap
+        .get(METADATA_VDXF_KEY)
+        .ok_or_else(|| format!("{} has no metadata in its contentmap", &name))?;
+
+    Ok((name, metadata_txid(metadata_tx)?))
+}
+
+async fn lookup(app_config: &Settings, number: i64) -> CliResult {
+    let client = verus_client(app_config)?;
+    let pool = database(app_config).await?;
+    let (name, metadata_tx) = identity_metadata_tx(app_config, &client, number)?;
+    let identity = client.get_identity(&name)?;
+
+    println!("identity: {}", &name);
+    println!(
+        "primary addresses: {}",
+        identity
+            .identity
+            .primaryaddresses
+            .iter()
+            .map(|address| address.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    println!("metadata: {}", &metadata_tx);
+
+    let (body, confirmed) = metadata_cache::get(&pool, number, &name, &metadata_tx).await?;
+    println!("confirmed on arweave: {}", confirmed);
+    println!("{}", serde_json::to_string_pretty(&body)?);
+
+    Ok(())
+}
+
+async fn verify(app_config: &Settings, number: i64) -> CliResult {
+    let client = verus_client(app_config)?;
+    let (name, metadata_tx) = identity_metadata_tx(app_config, &client, number)?;
+    println!("identity {} points at metadata {}", &name, &metadata_tx);
+
+    let mut failed = false;
+    let metadata_confirmations = arweave::get_transaction_confirmations(&metadata_tx).await?;
+    println!("metadata: {} confirmations", metadata_confirmations);
+    failed |= metadata_confirmations < 1;
+
+    let body: Value = arweave::get_metadata_json(&metadata_tx).await?;
+    match body["image"].as_str() {
+        Some(image_tx) => {
+            let image_confirmations = arweave::get_transaction_confirmations(image_tx).await?;
+            println!("image {}: {} confirmations", image_tx, image_confirmations);
+            failed |= image_confirmations < 1;
+        }
+        None => {
+            println!("the metadata has no image");
+            failed = true;
+        }
+    }
+
+    match failed {
+        true => Err(format!("{} is not fully on chain", &name).into()),
+        false => {
+            println!("{} is on chain", &name);
+            Ok(())
+        }
+    }
+}
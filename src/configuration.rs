@@ -1,11 +1,27 @@
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 
 #[derive(Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
+    #[serde(default)]
+    pub identity: IdentitySettings,
+    #[serde(default)]
+    pub market: MarketSettings,
+    #[serde(default)]
+    pub events: EventSettings,
+    #[serde(default)]
+    pub api: ApiSettings,
+    #[serde(default)]
+    pub roles: RoleSettings,
+    #[serde(default)]
+    pub admin: AdminSettings,
+    #[serde(default)]
+    pub announcements: AnnouncementSettings,
 }
 
 #[derive(Deserialize, Clone)]
@@ -53,6 +69,188 @@ pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub sequence_start: u64,
     pub series: String,
+    /// Runs the mint pipeline without spending anything: the metadata and the art are generated,
+    /// but the Arweave uploads and the identity registration are only logged and get made up ids.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// The default policy for every gecko sub-ID of the series. An authority is an identity name
+/// (`geckotest@`) or an i-address. When left out, the gecko becomes its own authority.
+#[derive(Deserialize, Clone, Default)]
+pub struct IdentitySettings {
+    pub revocation_authority: Option<String>,
+    pub recovery_authority: Option<String>,
+}
+
+/// Listings are posted in `channel_id`; without it the marketplace only works through commands.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct MarketSettings {
+    #[serde(deserialize_with = "deserialize_option_number_from_string")]
+    pub channel_id: Option<u64>,
+    // how long an offer stays open, in blocks
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub offer_expiry_blocks: u64,
+}
+
+impl Default for MarketSettings {
+    fn default() -> Self {
+        MarketSettings {
+            channel_id: None,
+            // about a week
+            offer_expiry_blocks: 10080,
+        }
+    }
+}
+
+/// Where the lifecycle events of the geckos go. Every subscriber can be limited to some `events`,
+/// e.g. `["minted", "sold"]`; without a list it receives all of them.
+#[derive(Deserialize, Clone, Default)]
+pub struct EventSettings {
+    #[serde(default)]
+    pub subscribers: Vec<SubscriberSettings>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SubscriberSettings {
+    Discord {
+        channel_id: u64,
+        #[serde(default)]
+        events: Vec<String>,
+    },
+    AuditLog {
+        #[serde(default)]
+        events: Vec<String>,
+    },
+    // payloads are signed with `secret`, see `lifecycle::webhooks`
+    Webhook {
+        url: String,
+        secret: Secret<String>,
+        #[serde(default)]
+        events: Vec<String>,
+    },
+}
+
+/// Discord roles that are granted to the members who meet a condition, and revoked when they no
+/// longer do. See `bot::roles`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RoleSettings {
+    pub rules: Vec<RoleRule>,
+    // besides the sync after every mint, transfer and sale
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub sync_interval_minutes: u64,
+}
+
+impl Default for RoleSettings {
+    fn default() -> Self {
+        RoleSettings {
+            rules: vec![],
+            sync_interval_minutes: 60,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RoleRule {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub role_id: u64,
+    #[serde(flatten)]
+    pub condition: RoleCondition,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RoleCondition {
+    // holds at least `min_count` geckos
+    Holder {
+        #[serde(default = "one")]
+        min_count: usize,
+    },
+    // holds a gecko that is among the `max_rank` rarest
+    Rank {
+        max_rank: i64,
+    },
+    // holds a gecko with this trait, e.g. `_alchemist` = `alchemist`
+    Trait {
+        trait_type: String,
+        value: String,
+    },
+    // has an open listing on the market
+    Seller,
+}
+
+fn one() -> usize {
+    1
+}
+
+/// The read-only HTTP API. It runs alongside the bot when `enabled`, or on its own with the `api`
+/// subcommand.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub listen_address: String,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        ApiSettings {
+            enabled: false,
+            listen_address: "127.0.0.1:8080".to_string(),
+        }
+    }
+}
+
+/// Who can use the `/admin` commands: members with one of `role_ids` and the users in `user_ids`.
+/// When both are empty, members with the Administrator permission can.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AdminSettings {
+    pub role_ids: Vec<u64>,
+    pub user_ids: Vec<u64>,
+}
+
+impl AdminSettings {
+    pub fn is_configured(&self) -> bool {
+        !self.role_ids.is_empty() || !self.user_ids.is_empty()
+    }
+}
+
+/// Where new geckos are announced, and what their holder is told in a DM. When the DM cannot be
+/// delivered, the holder is mentioned in the announcement channels with `fallback` instead. The
+/// templates fill in `{series}`, `{number}`, `{identity}` and `{member}`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct AnnouncementSettings {
+    pub channel_ids: Vec<u64>,
+    // the name of the series as shown to members, `application.series` is its Verus currency
+    pub series_name: String,
+    pub title: String,
+    pub welcome: String,
+    pub fallback: String,
+}
+
+impl Default for AnnouncementSettings {
+    fn default() -> Self {
+        AnnouncementSettings {
+            channel_ids: vec![],
+            series_name: "Goofy Geckos".to_string(),
+            title: "Introducing {series} #{number}".to_string(),
+            welcome: "Welcome! Your {series} #{number} has been minted as the Verus identity \
+                `{identity}`, and the bot holds it for you.\n\n\
+                - `/gecko number:{number}` shows it with its traits and rarity\n\
+                - `/withdraw` sends it to an address of your own, `/deposit` gives it back to \
+                the bot\n\
+                - `/sell`, `/bid` and `/market` trade it with the other holders"
+                .to_string(),
+            fallback: "{member} your {series} #{number} has been minted! Allow DMs from the \
+                server members to get the details."
+                .to_string(),
+        }
+    }
 }
 
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
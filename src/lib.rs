@@ -5,7 +5,10 @@
 extern crate derive_more;
 extern crate hex;
 
+pub mod api;
 pub mod bot;
+pub mod cli;
 pub mod configuration;
+pub mod lifecycle;
 mod nft;
 mod trader;
@@ -0,0 +1,212 @@
+/*
+NFT lifecycle events
+*/
+// The mint pipeline, the indexer and the marketplace publish what happens to a gecko on the event
+// bus. Subscribers, configured under `events.subscribers`, each receive every event on their own
+// task, so a slow subscriber does not hold up the publishers or the other subscribers.
+pub mod subscribers;
+pub mod webhooks;
+
+use serde::Serialize;
+use serenity::async_trait;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+use crate::trader::OfferSide;
+
+// events that are not received by a subscriber within this many newer events are dropped for it
+const CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MintStage {
+    Metadata,
+    Art,
+    ImageUpload,
+    MetadataUpload,
+    Identity,
+}
+
+impl MintStage {
+    /// The name of the stage as it is serialized and stored with a mint job.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MintStage::Metadata => "metadata",
+            MintStage::Art => "art",
+            MintStage::ImageUpload => "image_upload",
+            MintStage::MetadataUpload => "metadata_upload",
+            MintStage::Identity => "identity",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NftEvent {
+    MintStarted {
+        gecko_number: i64,
+        discord_user_id: u64,
+    },
+    StageCompleted {
+        gecko_number: i64,
+        stage: MintStage,
+    },
+    Minted {
+        gecko_number: i64,
+        identity: String,
+        discord_user_id: u64,
+        owner: String,
+        metadata_tx: Option<String>,
+        image_tx: Option<String>,
+    },
+    Transferred {
+        gecko_number: i64,
+        identity: String,
+        from: Vec<String>,
+        to: Vec<String>,
+        metadata_tx: Option<String>,
+        txid: String,
+        height: u64,
+    },
+    Listed {
+        gecko_number: i64,
+        identity: String,
+        side: OfferSide,
+        price: u64,
+        currency: String,
+        offer_txid: String,
+        discord_user_id: u64,
+    },
+    Sold {
+        gecko_number: i64,
+        identity: String,
+        price: u64,
+        currency: String,
+        offer_txid: String,
+        txid: Option<String>,
+    },
+    MintFailed {
+        gecko_number: i64,
+        discord_user_id: u64,
+        stage: MintStage,
+    },
+}
+
+impl NftEvent {
+    /// The name of the event as used in the configuration and the serialized payload.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NftEvent::MintStarted { .. } => "mint_started",
+            NftEvent::StageCompleted { .. } => "stage_completed",
+            NftEvent::Minted { .. } => "minted",
+            NftEvent::Transferred { .. } => "transferred",
+            NftEvent::Listed { .. } => "listed",
+            NftEvent::Sold { .. } => "sold",
+            NftEvent::MintFailed { .. } => "mint_failed",
+        }
+    }
+
+    pub fn gecko_number(&self) -> i64 {
+        match self {
+            NftEvent::MintStarted { gecko_number, .. }
+            | NftEvent::StageCompleted { gecko_number, .. }
+            | NftEvent::Minted { gecko_number, .. }
+            | NftEvent::Transferred { gecko_number, .. }
+            | NftEvent::Listed { gecko_number, .. }
+            | NftEvent::Sold { gecko_number, .. }
+            | NftEvent::MintFailed { gecko_number, .. } => *gecko_number,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<NftEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+
+        EventBus { sender }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: NftEvent) {
+        debug!("publishing {:?}", &event);
+
+        // without subscribers the event is simply dropped
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NftEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Runs a subscriber on its own task for as long as the bus exists.
+    pub fn register(&self, subscriber: Box<dyn Subscriber>) {
+        let mut receiver = self.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        if subscriber.wants(&event) {
+                            subscriber.handle(&event).await;
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("subscriber {} missed {} events", subscriber.name(), missed)
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+pub trait Subscriber: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn wants(&self, _event: &NftEvent) -> bool {
+        true
+    }
+
+    async fn handle(&self, event: &NftEvent);
+}
+
+/// Whether an event is in a configured list of event kinds. An empty list selects every event.
+pub fn selects(kinds: &[String], event: &NftEvent) -> bool {
+    kinds.is_empty() || kinds.iter().any(|kind| kind == event.kind())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{selects, MintStage, NftEvent};
+
+    #[test]
+    fn serialized_tag_is_the_kind() {
+        let event = NftEvent::StageCompleted {
+            gecko_number: 3,
+            stage: MintStage::ImageUpload,
+        };
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], event.kind());
+        assert_eq!(json["stage"], "image_upload");
+    }
+
+    #[test]
+    fn select_by_kind() {
+        let event = NftEvent::MintStarted {
+            gecko_number: 1,
+            discord_user_id: 2,
+        };
+
+        assert!(selects(&[], &event));
+        assert!(selects(&["mint_started".to_string()], &event));
+        assert!(!selects(&["sold".to_string()], &event));
+    }
+}
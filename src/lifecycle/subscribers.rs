@@ -0,0 +1,153 @@
+// The subscribers that can be configured under `events.subscribers`.
+use serenity::{async_trait, http::Http, model::id::ChannelId};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::error;
+
+use super::{selects, NftEvent, Subscriber};
+use crate::{
+    configuration::{EventSettings, SubscriberSettings},
+    trader::format_amount,
+};
+
+pub fn from_settings(
+    settings: &EventSettings,
+    http: Arc<Http>,
+    pool: PgPool,
+) -> Vec<Box<dyn Subscriber>> {
+    settings
+        .subscribers
+        .iter()
+        .filter_map(|subscriber| -> Option<Box<dyn Subscriber>> {
+            match subscriber {
+                SubscriberSettings::Discord { channel_id, events } => {
+                    Some(Box::new(DiscordAnnouncer {
+                        http: http.clone(),
+                        channel_id: ChannelId(*channel_id),
+                        events: events.clone(),
+                    }))
+                }
+                SubscriberSettings::AuditLog { events } => Some(Box::new(AuditLog {
+                    pool: pool.clone(),
+                    events: events.clone(),
+                })),
+                // webhooks are not on the bus, their deliveries are written to the outbox by
+                // whatever persists the event, see `webhooks::enqueue`
+                SubscriberSettings::Webhook { .. } => None,
+            }
+        })
+        .collect()
+}
+
+/// Posts a line about every event in a Discord channel.
+pub struct DiscordAnnouncer {
+    http: Arc<Http>,
+    channel_id: ChannelId,
+    events: Vec<String>,
+}
+
+#[async_trait]
+impl Subscriber for DiscordAnnouncer {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn wants(&self, event: &NftEvent) -> bool {
+        selects(&self.events, event)
+    }
+
+    async fn handle(&self, event: &NftEvent) {
+        if let Err(e) = self.channel_id.say(&self.http, describe(event)).await {
+            error!("could not announce {}: {:?}", event.kind(), e);
+        }
+    }
+}
+
+/// Keeps every event in the `event_log` table.
+pub struct AuditLog {
+    pool: PgPool,
+    events: Vec<String>,
+}
+
+#[async_trait]
+impl Subscriber for AuditLog {
+    fn name(&self) -> &'static str {
+        "audit_log"
+    }
+
+    fn wants(&self, event: &NftEvent) -> bool {
+        selects(&self.events, event)
+    }
+
+    async fn handle(&self, event: &NftEvent) {
+        let payload = match serde_json::to_string(event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("could not serialize {:?}: {:?}", event, e);
+                return;
+            }
+        };
+
+        if let Err(e) = sqlx::query!(
+            "INSERT INTO event_log (kind, gecko_number, payload) VALUES ($1, $2, $3)",
+            event.kind(),
+            event.gecko_number(),
+            payload
+        )
+        .execute(&self.pool)
+        .await
+        {
+            error!("Database write error: {:?}", e);
+        }
+    }
+}
+
+fn describe(event: &NftEvent) -> String {
+    match event {
+        NftEvent::MintStarted {
+            gecko_number,
+            discord_user_id,
+        } => format!("Minting gecko #{} for <@{}>", gecko_number, discord_user_id),
+        NftEvent::StageCompleted {
+            gecko_number,
+            stage,
+        } => format!("Gecko #{}: {:?} done", gecko_number, stage),
+        NftEvent::Minted {
+            identity,
+            discord_user_id,
+            ..
+        } => format!("`{}` was minted for <@{}>!", identity, discord_user_id),
+        NftEvent::Transferred { identity, to, .. } => {
+            format!("`{}` was transferred to `{}`", identity, to.join(", "))
+        }
+        NftEvent::Listed {
+            identity,
+            side,
+            price,
+            currency,
+            ..
+        } => format!(
+            "New {} on `{}`: {} {}",
+            side.as_str(),
+            identity,
+            format_amount(*price),
+            currency
+        ),
+        NftEvent::Sold {
+            identity,
+            price,
+            currency,
+            ..
+        } => format!(
+            "`{}` was sold for {} {}",
+            identity,
+            format_amount(*price),
+            currency
+        ),
+        NftEvent::MintFailed {
+            gecko_number,
+            stage,
+            ..
+        } => format!("Minting gecko #{} failed at {:?}", gecko_number, stage),
+    }
+}
@@ -0,0 +1,340 @@
+// Outbound webhooks for mints and transfers. A delivery is written to the `webhook_outbox` table
+// together with the mint or the transfer it is about; `deliver` signs and posts everything that is
+// due from there, so deliveries that failed or were still pending when the bot stopped are retried
+// after a restart.
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, error, info, warn};
+
+use super::{selects, NftEvent};
+use crate::{
+    bot::metadata_cache,
+    configuration::{EventSettings, SubscriberSettings},
+    nft::metadata::NFTMetadata,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 50;
+// a delivery is given up after this many failed attempts, which is about a day and a half of
+// retrying with `backoff`
+const MAX_ATTEMPTS: i32 = 12;
+const FIRST_RETRY: Duration = Duration::from_secs(30);
+const MAX_RETRY: Duration = Duration::from_secs(6 * 60 * 60);
+
+pub const SIGNATURE_HEADER: &str = "X-Gecko-Signature";
+pub const EVENT_HEADER: &str = "X-Gecko-Event";
+pub const DELIVERY_HEADER: &str = "X-Gecko-Delivery";
+
+/// The body of a webhook request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub event: String,
+    pub gecko_number: i64,
+    pub identity: String,
+    pub metadata_tx: Option<String>,
+    pub image_tx: Option<String>,
+    // the primary addresses of the gecko after the event
+    pub owner: Vec<String>,
+    // the transaction of a transfer
+    pub txid: Option<String>,
+    pub timestamp: u64,
+}
+
+impl WebhookPayload {
+    // only mints and transfers are sent out
+    fn from_event(event: &NftEvent) -> Option<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        match event {
+            NftEvent::Minted {
+                gecko_number,
+                identity,
+                owner,
+                metadata_tx,
+                image_tx,
+                ..
+            } => Some(WebhookPayload {
+                event: event.kind().to_string(),
+                gecko_number: *gecko_number,
+                identity: identity.clone(),
+                metadata_tx: metadata_tx.clone(),
+                image_tx: image_tx.clone(),
+                owner: vec![owner.clone()],
+                txid: None,
+                timestamp,
+            }),
+            NftEvent::Transferred {
+                gecko_number,
+                identity,
+                to,
+                metadata_tx,
+                txid,
+                ..
+            } => Some(WebhookPayload {
+                event: event.kind().to_string(),
+                gecko_number: *gecko_number,
+                identity: identity.clone(),
+                metadata_tx: metadata_tx.clone(),
+                image_tx: None,
+                owner: to.clone(),
+                txid: Some(txid.clone()),
+                timestamp,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The hex encoded HMAC-SHA256 of `body`, sent as `sha256=<signature>` in `SIGNATURE_HEADER`.
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes a key of any size");
+    mac.update(body.as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Doubles with every failed attempt, up to `MAX_RETRY`.
+fn backoff(attempts: i32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.max(1) as u32 - 1);
+
+    FIRST_RETRY.saturating_mul(factor).min(MAX_RETRY)
+}
+
+/// Adds a delivery of `event` to the outbox of every configured webhook that wants it. This is
+/// called in the transaction that persists the mint or the transfer, so a delivery is never lost
+/// once the event is stored. The payload is signed when it is first delivered.
+pub async fn enqueue(
+    transaction: &mut Transaction<'_, Postgres>,
+    settings: &EventSettings,
+    event: &NftEvent,
+) -> Result<(), sqlx::Error> {
+    let payload = match WebhookPayload::from_event(event) {
+        Some(payload) => payload,
+        None => return Ok(()),
+    };
+    let body = serde_json::to_string(&payload).expect("a webhook payload serializes");
+
+    for subscriber in &settings.subscribers {
+        if let SubscriberSettings::Webhook { url, events, .. } = subscriber {
+            if !selects(events, event) {
+                continue;
+            }
+
+            sqlx::query!(
+                "INSERT INTO webhook_outbox (url, event, payload) VALUES ($1, $2, $3)",
+                url,
+                event.kind(),
+                body
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+// A transfer does not know the image, but the metadata does. Looking it up can take a request to
+// Arweave, which is why it happens here and not while the event is persisted.
+async fn complete(pool: &PgPool, payload: &mut WebhookPayload) {
+    if let (None, Some(metadata_tx)) = (&payload.image_tx, &payload.metadata_tx) {
+        match metadata_cache::get(pool, payload.gecko_number, &payload.identity, metadata_tx).await
+        {
+            Ok((raw_json, _)) => {
+                payload.image_tx = serde_json::from_value::<NFTMetadata>(raw_json)
+                    .ok()
+                    .map(|metadata| metadata.image)
+            }
+            Err(e) => debug!("no metadata for {}: {:?}", &payload.identity, e),
+        }
+    }
+}
+
+/// Posts every delivery in the outbox that is due, forever.
+pub async fn deliver(pool: PgPool, settings: EventSettings) {
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            error!("could not create the webhook client: {:?}", e);
+            return;
+        }
+    };
+
+    loop {
+        if let Err(e) = deliver_due(&pool, &client, &settings).await {
+            error!("webhook delivery failed: {:?}", e);
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn deliver_due(
+    pool: &PgPool,
+    client: &reqwest::Client,
+    settings: &EventSettings,
+) -> Result<(), sqlx::Error> {
+    let due = sqlx::query!(
+        "SELECT id, url, event, payload, signature, attempts FROM webhook_outbox WHERE status = 'pending' AND next_attempt_at <= now() ORDER BY id LIMIT $1",
+        BATCH_SIZE
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for delivery in due {
+        // the body is signed once, so every retry sends the same body and signature
+        let (payload, signature) = match delivery.signature {
+            Some(signature) => (delivery.payload, signature),
+            None => match sign_delivery(pool, settings, &delivery.url, &delivery.payload).await {
+                Ok((payload, signature)) => {
+                    sqlx::query!(
+                        "UPDATE webhook_outbox SET payload = $2, signature = $3 WHERE id = $1",
+                        delivery.id,
+                        payload,
+                        signature
+                    )
+                    .execute(pool)
+                    .await?;
+
+                    (payload, signature)
+                }
+                Err(e) => {
+                    warn!(
+                        "giving up on webhook {} to {}: {}",
+                        delivery.id, &delivery.url, e
+                    );
+                    sqlx::query!(
+                        "UPDATE webhook_outbox SET status = 'failed', last_error = $2 WHERE id = $1",
+                        delivery.id,
+                        e
+                    )
+                    .execute(pool)
+                    .await?;
+
+                    continue;
+                }
+            },
+        };
+
+        let result = client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, format!("sha256={}", &signature))
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .body(payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        let attempts = delivery.attempts + 1;
+        match result {
+            Ok(_) => {
+                debug!("delivered webhook {} to {}", delivery.id, &delivery.url);
+                sqlx::query!(
+                    "UPDATE webhook_outbox SET status = 'delivered', attempts = $2, delivered_at = now() WHERE id = $1",
+                    delivery.id,
+                    attempts
+                )
+                .execute(pool)
+                .await?;
+            }
+            Err(e) if attempts >= MAX_ATTEMPTS => {
+                warn!(
+                    "giving up on webhook {} to {}: {:?}",
+                    delivery.id, &delivery.url, e
+                );
+                sqlx::query!(
+                    "UPDATE webhook_outbox SET status = 'failed', attempts = $2, last_error = $3 WHERE id = $1",
+                    delivery.id,
+                    attempts,
+                    e.to_string()
+                )
+                .execute(pool)
+                .await?;
+            }
+            Err(e) => {
+                let retry = backoff(attempts);
+                info!(
+                    "webhook {} to {} failed, retrying in {}s: {:?}",
+                    delivery.id,
+                    &delivery.url,
+                    retry.as_secs(),
+                    e
+                );
+                sqlx::query!(
+                    "UPDATE webhook_outbox SET attempts = $2, last_error = $3, next_attempt_at = now() + make_interval(secs => $4) WHERE id = $1",
+                    delivery.id,
+                    attempts,
+                    e.to_string(),
+                    retry.as_secs_f64()
+                )
+                .execute(pool)
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Completes the payload and signs it with the secret of the webhook at `url`.
+async fn sign_delivery(
+    pool: &PgPool,
+    settings: &EventSettings,
+    url: &str,
+    payload: &str,
+) -> Result<(String, String), String> {
+    let secret = settings
+        .subscribers
+        .iter()
+        .find_map(|subscriber| match subscriber {
+            SubscriberSettings::Webhook {
+                url: webhook_url,
+                secret,
+                ..
+            } if webhook_url == url => Some(secret),
+            _ => None,
+        })
+        .ok_or_else(|| String::from("the webhook is no longer configured"))?;
+
+    let mut payload = serde_json::from_str::<WebhookPayload>(payload)
+        .map_err(|e| format!("invalid payload: {}", e))?;
+    complete(pool, &mut payload).await;
+
+    let body = serde_json::to_string(&payload).map_err(|e| format!("invalid payload: {}", e))?;
+    let signature = sign(secret.expose_secret(), &body);
+
+    Ok((body, signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff, sign, FIRST_RETRY, MAX_RETRY};
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        assert_eq!(backoff(1), FIRST_RETRY);
+        assert_eq!(backoff(2), FIRST_RETRY * 2);
+        assert_eq!(backoff(4), FIRST_RETRY * 8);
+        assert_eq!(backoff(30), MAX_RETRY);
+    }
+}
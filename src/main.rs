@@ -1,5 +1,6 @@
 extern crate verusnftlib;
 
+use clap::Parser;
 use color_eyre::Report;
 use secrecy::ExposeSecret;
 use serenity::{
@@ -8,23 +9,36 @@ use serenity::{
     model::{channel::Message, gateway::GatewayIntents},
 };
 use std::{path::Path, sync::Arc};
-use tracing::{debug, error, instrument};
+use tracing::{debug, error, info, instrument};
 use tracing_subscriber::filter::EnvFilter;
 use verusnftlib::{
-    bot::{events, framework::*, global_data::*, utils::database::*},
+    api,
+    bot::{events, framework::*, global_data::*},
+    cli::{self, Cli, Command},
     configuration::*,
+    lifecycle::EventBus,
 };
 use vrsc_rpc::RpcApi;
 
 #[tokio::main(worker_threads = 8)]
 #[instrument]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let cli = Cli::parse();
     let config = get_configuration().expect("failed to read configuration");
 
     setup_logging().await?;
 
+    match cli.command {
+        None | Some(Command::Bot) => {}
+        Some(command) => return cli::run(command, config).await,
+    }
+
+    let pg_pool = cli::database(&config).await?;
+
     let ardrive_wallet_location = &config.application.ardrive_wallet_location;
-    if !Path::new(ardrive_wallet_location).exists() {
+    if config.application.dry_run {
+        info!("dry run: nothing is uploaded to Arweave or registered on Verus");
+    } else if !Path::new(ardrive_wallet_location).exists() {
         error!("ardrivewallet not found");
         return Ok(());
     }
@@ -46,7 +60,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .on_dispatch_error(on_dispatch_error)
         .group(&GENERAL_GROUP);
 
-    let handler = Arc::new(events::Handler {});
+    let handler = Arc::new(events::Handler::default());
 
     let mut intents = GatewayIntents::all();
     intents.remove(GatewayIntents::DIRECT_MESSAGE_TYPING);
@@ -63,9 +77,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let mut data = client.data.write().await;
         data.insert::<AppConfig>(config.clone());
 
-        let pg_pool = obtain_postgres_pool(&config.database).await?;
-        sqlx::migrate!("./migrations").run(&pg_pool).await?;
-        data.insert::<DatabasePool>(pg_pool);
+        data.insert::<DatabasePool>(pg_pool.clone());
+
+        data.insert::<Bus>(EventBus::default());
+    }
+
+    if config.api.enabled {
+        tokio::spawn(async move {
+            if let Err(e) = api::serve(config, pg_pool).await {
+                error!("the API stopped: {:?}", e);
+            }
+        });
     }
 
     debug!("starting client");
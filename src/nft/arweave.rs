@@ -7,7 +7,7 @@ use reqwest::{
     Method, Response,
 };
 use std::path::{Path, PathBuf};
-use tracing::debug;
+use tracing::{debug, info};
 use url::Url;
 
 pub struct ArweaveTransaction {
@@ -88,6 +88,38 @@ impl ArweaveTransaction {
     }
 }
 
+/// Logs the upload that `ArweaveTransaction::upload` would do and returns a made up transaction id,
+/// which has the length and the encoding of a real one.
+pub fn simulate_upload(file_location: &Path, tags: &[(&str, &str)]) -> String {
+    let tx = base64_url::encode(&rand::random::<[u8; 32]>());
+    info!(
+        "dry run: not uploading {} to Arweave with tags {:?}, simulated as {}",
+        file_location.display(),
+        tags,
+        &tx
+    );
+
+    tx
+}
+
+/// The balance of the wallet of a keypair file, in AR.
+pub async fn wallet_balance(keypair_location: &Path) -> Result<f64, ArweaveError> {
+    let arweave = Arweave::from_keypair_path(
+        keypair_location.into(),
+        Url::parse("https://arweave.net").unwrap(),
+    )
+    .await
+    .map_err(|e| ErrorKind::Wallet(e.to_string()))?;
+
+    let winstons = arweave
+        .get_wallet_balance(None)
+        .await
+        .map_err(|e| ErrorKind::Wallet(e.to_string()))?;
+
+    // 1 AR is 10^12 winstons
+    Ok(winstons.to_string().parse::<f64>().unwrap_or_default() / 1e12)
+}
+
 pub async fn get_transaction_by_gecko_number(gecko_number: i64) -> String {
     let identity_name = format!("{}.geckotest@", gecko_number);
 
@@ -142,7 +174,9 @@ pub async fn get_transaction_by_gecko_number(gecko_number: i64) -> String {
 pub async fn get_metadata_json<'a>(tx_id: &'a str) -> Result<serde_json::Value, ArweaveError> {
     // first check for status. If unconfirmed, return error
     // then get data, it should exist since it was confirmed, but could still go wrong of course.
-    let _ = get_transaction_confirmations(tx_id).await?;
+    if get_transaction_confirmations(tx_id).await? < 1 {
+        return Err(ErrorKind::NotConfirmed.into());
+    }
 
     // at this point we know the arweave tx is confirmed.
     debug!("getting metadata");
@@ -221,11 +255,20 @@ pub enum ErrorKind {
     InvalidJson(String),
     NotConfirmed,
     NoData,
+    Wallet(String),
     ReqwestError(reqwest::Error),
     JsonError(serde_json::Error),
     Base64DecodeError(base64_url::base64::DecodeError),
 }
 
+impl std::error::Error for ArweaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|boxed| boxed.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
 impl From<ErrorKind> for ArweaveError {
     fn from(kind: ErrorKind) -> Self {
         ArweaveError { kind, source: None }
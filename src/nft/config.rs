@@ -1,7 +1,7 @@
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Result;
-use std::{collections::BTreeMap, fs};
+use std::{collections::BTreeMap, fs, path::Path};
 
 pub fn parse(location: &str) -> Result<Config> {
     let config_file = fs::read_to_string(location).expect("Could not read configuration file");
@@ -36,3 +36,107 @@ pub struct Creator {
     pub address: String,
     pub share: u8,
 }
+
+/// What would make the generation panic or leave a layer out: weights that cannot be drawn from,
+/// and options without an image in the directory of their trait. Traits without a directory are
+/// selectors and have no layers.
+pub fn validate(config: &Config, assets_directory: &Path) -> Vec<String> {
+    let mut problems = vec![];
+
+    for (attribute_name, keys) in &config.attributes {
+        let standard = keys
+            .iter()
+            .filter_map(|(key, attribute)| match attribute {
+                Attribute::Standard(weight) => Some((key.clone(), *weight)),
+                Attribute::Keyed(_) => None,
+            })
+            .collect::<IndexMap<_, _>>();
+        problems.extend(check_weights(attribute_name, &standard));
+
+        let layers = assets_directory.join(attribute_name);
+        let mut options = standard.keys().cloned().collect::<Vec<_>>();
+
+        for (key, attribute) in keys {
+            if let Attribute::Keyed(weights) = attribute {
+                problems.extend(check_weights(
+                    &format!("{} ({})", attribute_name, key),
+                    weights,
+                ));
+                options.extend(weights.keys().cloned());
+            }
+        }
+
+        if layers.is_dir() {
+            for option in options {
+                let name = option.strip_suffix(".png").unwrap_or(&option);
+                if !layers.join(format!("{}.png", name)).exists() {
+                    problems.push(format!(
+                        "`{}` has no image for `{}` in {}",
+                        attribute_name,
+                        name,
+                        layers.display()
+                    ));
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+// A weighted draw needs at least one positive weight and no negative ones.
+fn check_weights(attribute_name: &str, weights: &IndexMap<String, f32>) -> Vec<String> {
+    let mut problems = weights
+        .iter()
+        .filter(|(_, weight)| **weight < 0.0)
+        .map(|(key, weight)| {
+            format!(
+                "`{}` has a negative weight {} for `{}`",
+                attribute_name, weight, key
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if !weights.values().any(|weight| *weight > 0.0) {
+        problems.push(format!("`{}` has nothing to draw from", attribute_name));
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, Config};
+    use serde_json::json;
+    use std::path::Path;
+
+    #[test]
+    fn weights_that_cannot_be_drawn_are_reported() {
+        let config: Config = serde_json::from_value(json!({
+            "name": "Geckos",
+            "identity": "geckos@",
+            "description": "",
+            "attributes": {
+                "eyes": { "normal.png": 1.0 },
+                "mouth": { "smile.png": 0.0 },
+                "base": {
+                    "_alchemist:alchemist": { "tokay.png": -1.0 }
+                }
+            }
+        }))
+        .unwrap();
+
+        let problems = validate(&config, Path::new("./does-not-exist"));
+
+        assert_eq!(
+            problems,
+            vec![
+                "`mouth` has nothing to draw from".to_string(),
+                "`base` has nothing to draw from".to_string(),
+                "`base (_alchemist:alchemist)` has a negative weight -1 for `tokay.png`"
+                    .to_string(),
+                "`base (_alchemist:alchemist)` has nothing to draw from".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+// Renders the metadata of a gecko in the shapes that other tools expect. What is stored on Arweave
+// is `NFTMetadata`, which is close to Metaplex; wallets and marketplaces outside of Solana mostly
+// read the OpenSea `tokenURI` shape, and Verus clients look in the contentmap of the identity.
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use tracing::error;
+
+use super::metadata::NFTMetadata;
+use crate::bot::sales::METADATA_VDXF_KEY;
+
+const ARWEAVE_GATEWAY: &str = "https://arweave.net";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataFormat {
+    Metaplex,
+    OpenSea,
+    Verus,
+}
+
+impl MetadataFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetadataFormat::Metaplex => "metaplex",
+            MetadataFormat::OpenSea => "opensea",
+            MetadataFormat::Verus => "verus",
+        }
+    }
+}
+
+impl FromStr for MetadataFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "metaplex" => Ok(MetadataFormat::Metaplex),
+            "opensea" => Ok(MetadataFormat::OpenSea),
+            "verus" => Ok(MetadataFormat::Verus),
+            other => Err(format!(
+                "{} is not a metadata format, use metaplex, opensea or verus",
+                other
+            )),
+        }
+    }
+}
+
+/// What is known about a gecko besides its metadata.
+pub struct Subject<'a> {
+    pub gecko_number: i64,
+    pub identity: &'a str,
+    pub identity_address: &'a str,
+    // the arweave transaction of the metadata
+    pub metadata_tx: &'a str,
+}
+
+/// The collection, from the asset configuration.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Collection {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub external_url: Option<String>,
+}
+
+impl Collection {
+    pub fn load(assets_dir: &str) -> Option<Self> {
+        let location = format!("{}/config.json", assets_dir);
+        let collection = std::fs::read_to_string(&location)
+            .map_err(|e| e.to_string())
+            .and_then(|file| serde_json::from_str(&file).map_err(|e| e.to_string()));
+
+        match collection {
+            Ok(collection) => Some(collection),
+            Err(e) => {
+                error!("could not read the collection from {}: {}", &location, e);
+                None
+            }
+        }
+    }
+
+    /// The document OpenSea reads from `contractURI`.
+    pub fn contract_metadata(&self) -> Value {
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "external_link": self.external_url,
+        })
+    }
+}
+
+fn image_url(image_tx: &str) -> String {
+    format!("{}/{}", ARWEAVE_GATEWAY, image_tx)
+}
+
+impl MetadataFormat {
+    pub fn render(
+        &self,
+        metadata: &NFTMetadata,
+        subject: &Subject,
+        collection: Option<&Collection>,
+    ) -> Value {
+        let external_url = collection.and_then(|collection| collection.external_url.as_deref());
+
+        match self {
+            MetadataFormat::Metaplex => json!({
+                "name": metadata.name,
+                "description": metadata.description,
+                "image": image_url(&metadata.image),
+                "external_url": external_url,
+                "attributes": metadata
+                    .attributes
+                    .iter()
+                    .map(|t| json!({ "trait_type": t.trait_type, "value": t.value }))
+                    .collect::<Vec<_>>(),
+                "properties": {
+                    "files": [{ "uri": image_url(&metadata.image), "type": "image/png" }],
+                    "category": "image",
+                },
+                "collection": collection.map(|collection| json!({ "name": collection.name })),
+            }),
+            MetadataFormat::OpenSea => {
+                let mut attributes = metadata
+                    .attributes
+                    .iter()
+                    .map(|t| json!({ "trait_type": t.trait_type, "value": t.value }))
+                    .collect::<Vec<_>>();
+                attributes.push(json!({
+                    "display_type": "number",
+                    "trait_type": "rarity",
+                    "value": metadata.rarity,
+                }));
+
+                json!({
+                    "name": metadata.name,
+                    "description": metadata.description,
+                    "image": image_url(&metadata.image),
+                    "external_url": external_url,
+                    "attributes": attributes,
+                })
+            }
+            // the identity as a Verus client would put it together, with the metadata under
+            // the key that the bot writes it to
+            MetadataFormat::Verus => json!({
+                "identity": subject.identity,
+                "identityaddress": subject.identity_address,
+                "contentmap": {
+                    METADATA_VDXF_KEY: base64_url::decode(subject.metadata_tx).ok().map(hex::encode),
+                },
+                METADATA_VDXF_KEY: {
+                    "name": metadata.name,
+                    "description": metadata.description,
+                    "image": format!("ar://{}", &metadata.image),
+                    "attributes": metadata
+                        .attributes
+                        .iter()
+                        .map(|t| (t.trait_type.clone(), json!(t.value)))
+                        .collect::<serde_json::Map<_, _>>(),
+                },
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetadataFormat, Subject};
+    use crate::{bot::sales::METADATA_VDXF_KEY, nft::metadata::NFTMetadata};
+    use serde_json::json;
+
+    fn metadata() -> NFTMetadata {
+        serde_json::from_value(json!({
+            "name": "Goofy Gecko #12",
+            "identity": "12.geckos",
+            "description": "A gecko",
+            "rarity": 0.5,
+            "image": "imagetx",
+            "edition": 0,
+            "attributes": [{ "trait_type": "eyes", "value": "heart-eyes", "rarity": 0.5 }],
+            "properties": { "files": [], "category": "image" }
+        }))
+        .unwrap()
+    }
+
+    const SUBJECT: Subject = Subject {
+        gecko_number: 12,
+        identity: "12.geckos@",
+        identity_address: "iGecko12",
+        metadata_tx: "AAEC",
+    };
+
+    #[test]
+    fn opensea_has_an_image_url_and_traits() {
+        let rendered = MetadataFormat::OpenSea.render(&metadata(), &SUBJECT, None);
+
+        assert_eq!(rendered["image"], "https://arweave.net/imagetx");
+        assert_eq!(rendered["attributes"][0]["trait_type"], "eyes");
+        assert_eq!(rendered["attributes"][1]["display_type"], "number");
+    }
+
+    #[test]
+    fn verus_stores_the_metadata_tx_as_hex() {
+        let rendered = MetadataFormat::Verus.render(&metadata(), &SUBJECT, None);
+
+        assert_eq!(rendered["contentmap"][METADATA_VDXF_KEY], "000102");
+        assert_eq!(
+            rendered[METADATA_VDXF_KEY]["attributes"]["eyes"],
+            "heart-eyes"
+        );
+    }
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!(
+            "OpenSea".parse::<MetadataFormat>(),
+            Ok(MetadataFormat::OpenSea)
+        );
+        assert!("erc1155".parse::<MetadataFormat>().is_err());
+    }
+}
@@ -1,10 +1,10 @@
-use std::{error::Error, thread, time::Duration};
+use std::{error::Error, fmt, str::FromStr, thread, time::Duration};
 use tracing::*;
 
 use vrsc_rpc::{
     bitcoin::Txid,
     json::{identity::NameCommitment, vrsc::Address},
-    jsonrpc::serde_json::Value,
+    jsonrpc::serde_json::{json, Value},
     Client, RpcApi,
 };
 
@@ -14,7 +14,8 @@ use vrsc_rpc::{
 /// 3. registering the actual identity
 #[derive(Debug)]
 pub struct Identity {
-    pub name_commitment: NameCommitment,
+    // there is none for a simulated registration
+    pub name_commitment: Option<NameCommitment>,
     pub registration_txid: Txid,
 }
 impl Identity {
@@ -28,8 +29,173 @@ impl Identity {
             addresses: None,
             private_address: None,
             content_map: None,
+            revocation_authority: None,
+            recovery_authority: None,
         }
     }
+
+    /// Starts an update of an identity that already exists on chain. Fields that are not set on the
+    /// returned builder keep their current on-chain value.
+    pub fn update(name: &str) -> IdentityUpdateBuilder {
+        IdentityUpdateBuilder::new(name, UpdateMethod::Update)
+    }
+
+    /// Starts a recovery of a revoked identity. This needs to be signed by the recovery authority.
+    pub fn recover(name: &str) -> IdentityUpdateBuilder {
+        IdentityUpdateBuilder::new(name, UpdateMethod::Recover)
+    }
+
+    /// Revokes an identity. This needs to be signed by the revocation authority, so it only works
+    /// when the wallet of the daemon controls that authority.
+    pub fn revoke(name: &str, testnet: bool) -> Result<Txid, IdentityError> {
+        let client = match testnet {
+            true => Client::chain("vrsctest", vrsc_rpc::Auth::ConfigFile, None),
+            false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
+        }?;
+
+        let txid = client.call("revokeidentity", &[json!(name)])?;
+        info!("identity `{}` has been revoked (txid: {})", name, &txid);
+
+        Ok(txid)
+    }
+
+    /// Puts an identity in the Verus Vault. A locked identity can not spend or be spent from until
+    /// it is unlocked. This needs to be signed by the primary addresses of the identity.
+    pub fn set_timelock(
+        name: &str,
+        timelock: Timelock,
+        testnet: bool,
+    ) -> Result<Txid, IdentityError> {
+        let client = match testnet {
+            true => Client::chain("vrsctest", vrsc_rpc::Auth::ConfigFile, None),
+            false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
+        }?;
+
+        let txid = client.call(
+            "setidentitytimelock",
+            &[json!(name), timelock.to_rpc_argument()],
+        )?;
+        info!(
+            "identity `{}` timelock set to {:?} (txid: {})",
+            name, timelock, &txid
+        );
+
+        Ok(txid)
+    }
+
+    /// Starts the unlock countdown of an identity that was locked with an unlock delay.
+    pub fn unlock(name: &str, testnet: bool) -> Result<Txid, IdentityError> {
+        let client = match testnet {
+            true => Client::chain("vrsctest", vrsc_rpc::Auth::ConfigFile, None),
+            false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
+        }?;
+
+        let height: u64 = client.call("getblockcount", &[])?;
+
+        Identity::set_timelock(name, Timelock::UnlockAtBlock(height), testnet)
+    }
+
+    /// Sends an update of a co-owned identity that got its last signature from the co-owner.
+    /// `partial_hex` is the update as this wallet signed it: the co-owner may only have added
+    /// signatures, so the inputs and outputs must be the same and it must update `name`.
+    pub fn send_cosigned(
+        name: &str,
+        partial_hex: &str,
+        signed_hex: &str,
+        testnet: bool,
+    ) -> Result<Txid, IdentityError> {
+        let client = match testnet {
+            true => Client::chain("vrsctest", vrsc_rpc::Auth::ConfigFile, None),
+            false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
+        }?;
+
+        let partial: Value = client.call("decoderawtransaction", &[json!(partial_hex)])?;
+        let signed: Value = client.call("decoderawtransaction", &[json!(signed_hex)])?;
+        if !same_transaction(&partial, &signed) {
+            return Err(ErrorKind::Other(String::from(
+                "the transaction is not the update that was signed by the bot",
+            ))
+            .into());
+        }
+
+        let identity: Value = client.call("getidentity", &[json!(name)])?;
+        let identity_address = identity["identity"]["identityaddress"]
+            .as_str()
+            .ok_or_else(|| ErrorKind::Other(format!("identity {} not found", name)))?;
+        if !updates_identity(&signed, identity_address) {
+            return Err(
+                ErrorKind::Other(format!("the transaction does not update {}", name)).into(),
+            );
+        }
+
+        let txid: Txid = client.call("sendrawtransaction", &[json!(signed_hex)])?;
+        if signed["txid"].as_str() != Some(txid.to_string().as_str()) {
+            return Err(ErrorKind::Other(format!(
+                "the daemon sent {} instead of {}",
+                txid, signed["txid"]
+            ))
+            .into());
+        }
+
+        Ok(txid)
+    }
+
+    pub fn vault_state(name: &str, testnet: bool) -> Result<VaultState, IdentityError> {
+        let client = match testnet {
+            true => Client::chain("vrsctest", vrsc_rpc::Auth::ConfigFile, None),
+            false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
+        }?;
+
+        let height: u64 = client.call("getblockcount", &[])?;
+        let identity: Value = client.call("getidentity", &[json!(name)])?;
+
+        let flags = identity["identity"]["flags"].as_u64().unwrap_or(0);
+        let timelock = identity["identity"]["timelock"].as_u64().unwrap_or(0);
+
+        Ok(VaultState::from_identity(flags, timelock, height))
+    }
+}
+
+// Two decoded transactions spend the same outputs into the same outputs, whatever their signatures.
+fn same_transaction(a: &Value, b: &Value) -> bool {
+    let inputs = |tx: &Value| {
+        tx["vin"].as_array().map(|vin| {
+            vin.iter()
+                .map(|input| (input["txid"].clone(), input["vout"].clone()))
+                .collect::<Vec<_>>()
+        })
+    };
+    let outputs = |tx: &Value| {
+        tx["vout"].as_array().map(|vout| {
+            vout.iter()
+                .map(|output| {
+                    (
+                        output["valueSat"].clone(),
+                        output["scriptPubKey"]["hex"].clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+
+    inputs(a).is_some()
+        && inputs(a) == inputs(b)
+        && outputs(a).is_some()
+        && outputs(a) == outputs(b)
+        && a["locktime"] == b["locktime"]
+        && a["expiryheight"] == b["expiryheight"]
+}
+
+fn updates_identity(tx: &Value, identity_address: &str) -> bool {
+    tx["vout"]
+        .as_array()
+        .map(|vout| {
+            vout.iter().any(|output| {
+                output["scriptPubKey"]["identityprimary"]["identityaddress"].as_str()
+                    == Some(identity_address)
+            })
+        })
+        .unwrap_or(false)
 }
 
 #[derive(Debug)]
@@ -43,6 +209,10 @@ pub struct IdentityBuilder {
     addresses: Option<Vec<Address>>,
     private_address: Option<String>,
     content_map: Option<Value>,
+    // defaults to the identity itself
+    revocation_authority: Option<String>,
+    // defaults to the identity itself
+    recovery_authority: Option<String>,
 }
 
 impl IdentityBuilder {
@@ -102,6 +272,18 @@ impl IdentityBuilder {
         self
     }
 
+    pub fn revocation_authority(&mut self, s: &str) -> &mut Self {
+        self.revocation_authority = Some(String::from(s));
+
+        self
+    }
+
+    pub fn recovery_authority(&mut self, s: &str) -> &mut Self {
+        self.recovery_authority = Some(String::from(s));
+
+        self
+    }
+
     pub fn validate(&mut self) -> Result<&mut Self, IdentityError> {
         if let (Some(min_sigs), Some(addresses)) =
             (self.minimum_signatures, self.addresses.as_ref())
@@ -120,6 +302,13 @@ impl IdentityBuilder {
             return Err(ErrorKind::Other(String::from("No identity name was given")).into());
         }
 
+        for authority in [&self.revocation_authority, &self.recovery_authority]
+            .into_iter()
+            .flatten()
+        {
+            validate_authority(authority)?;
+        }
+
         if self.addresses.is_none() || self.addresses.as_ref().unwrap().is_empty() {
             return Err(ErrorKind::Other(String::from(
                 "no primary address given, need at least 1",
@@ -193,10 +382,35 @@ impl IdentityBuilder {
 
         Ok(Identity {
             registration_txid,
-            name_commitment,
+            name_commitment: Some(name_commitment),
         })
     }
 
+    /// Logs the name commitment and the registration that `create_identity` would send, and returns
+    /// an identity with a made up registration transaction.
+    pub fn simulate_identity(&self) -> Identity {
+        info!(
+            "dry run: not calling registernamecommitment for `{}` under `{}` with address {}",
+            self.name.as_deref().unwrap_or_default(),
+            self.currency_name.as_deref().unwrap_or_default(),
+            self.addresses
+                .as_ref()
+                .and_then(|addresses| addresses.first())
+                .map(|address| address.to_string())
+                .unwrap_or_default()
+        );
+        info!(
+            "dry run: not calling registeridentity with {}",
+            self.identity_definition()
+        );
+
+        Identity {
+            name_commitment: None,
+            registration_txid: Txid::from_str(&hex::encode(rand::random::<[u8; 32]>()))
+                .expect("32 bytes of hex is a txid"),
+        }
+    }
+
     async fn register_name_commitment(&mut self) -> Result<NameCommitment, IdentityError> {
         let client = match self.testnet {
             false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
@@ -250,14 +464,26 @@ impl IdentityBuilder {
             false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
         }?;
 
-        let id_txid = client.registeridentity(
-            &namecommitment,
-            self.addresses.as_ref().unwrap(),
-            self.minimum_signatures,
-            self.private_address.clone(),
-            self.currency_name.clone(),
-            self.content_map.clone(),
-        )?;
+        // the RPC wrapper has no parameters for the authorities, so in that case the full
+        // registration object is built here and sent as is.
+        let id_txid = match (&self.revocation_authority, &self.recovery_authority) {
+            (None, None) => client.registeridentity(
+                &namecommitment,
+                self.addresses.as_ref().unwrap(),
+                self.minimum_signatures,
+                self.private_address.clone(),
+                self.currency_name.clone(),
+                self.content_map.clone(),
+            )?,
+            _ => client.call(
+                "registeridentity",
+                &[json!({
+                    "txid": namecommitment.txid,
+                    "namereservation": namecommitment.namereservation,
+                    "identity": self.identity_definition(),
+                })],
+            )?,
+        };
         debug!("{:?}", id_txid);
 
         info!(
@@ -267,6 +493,330 @@ impl IdentityBuilder {
 
         Ok(id_txid)
     }
+
+    fn identity_definition(&self) -> Value {
+        let mut identity = json!({
+            "name": self.name,
+            "primaryaddresses": self
+                .addresses
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|address| address.to_string())
+                .collect::<Vec<_>>(),
+            "minimumsignatures": self.minimum_signatures.unwrap_or(1),
+        });
+
+        let fields = [
+            ("parent", self.currency_name.clone().map(Value::from)),
+            (
+                "privateaddress",
+                self.private_address.clone().map(Value::from),
+            ),
+            ("contentmap", self.content_map.clone()),
+            (
+                "revocationauthority",
+                self.revocation_authority.clone().map(Value::from),
+            ),
+            (
+                "recoveryauthority",
+                self.recovery_authority.clone().map(Value::from),
+            ),
+        ];
+
+        for (key, value) in fields {
+            if let Some(value) = value {
+                identity[key] = value;
+            }
+        }
+
+        identity
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timelock {
+    // the identity stays locked until an unlock is requested, after which it takes this many blocks
+    Delay(u64),
+    // the identity is locked until this block height
+    UnlockAtBlock(u64),
+}
+
+impl Timelock {
+    fn to_rpc_argument(self) -> Value {
+        match self {
+            Timelock::Delay(blocks) => json!({ "setunlockdelay": blocks }),
+            Timelock::UnlockAtBlock(height) => json!({ "unlockatblock": height }),
+        }
+    }
+}
+
+// the identity flag that is set when an identity is locked with an unlock delay
+const IDENTITY_FLAG_LOCKED: u64 = 0x02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VaultState {
+    Unlocked,
+    Locked { delay: u64 },
+    Unlocking { blocks_left: u64 },
+}
+
+impl VaultState {
+    pub fn from_identity(flags: u64, timelock: u64, height: u64) -> Self {
+        if flags & IDENTITY_FLAG_LOCKED != 0 {
+            VaultState::Locked { delay: timelock }
+        } else if timelock > height {
+            VaultState::Unlocking {
+                blocks_left: timelock - height,
+            }
+        } else {
+            VaultState::Unlocked
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        !matches!(self, VaultState::Unlocked)
+    }
+}
+
+impl fmt::Display for VaultState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VaultState::Unlocked => write!(f, "Unlocked"),
+            VaultState::Locked { delay } => write!(
+                f,
+                "Locked, unlocks {} blocks (~{}) after an unlock",
+                delay,
+                approximate_duration(*delay)
+            ),
+            VaultState::Unlocking { blocks_left } => write!(
+                f,
+                "Unlocking, {} blocks (~{}) left",
+                blocks_left,
+                approximate_duration(*blocks_left)
+            ),
+        }
+    }
+}
+
+// Verus aims for a block every minute.
+fn approximate_duration(blocks: u64) -> String {
+    match blocks {
+        0..=119 => format!("{} minutes", blocks),
+        120..=2879 => format!("{} hours", blocks / 60),
+        _ => format!("{} days", blocks / 1440),
+    }
+}
+
+#[derive(Debug)]
+enum UpdateMethod {
+    Update,
+    Recover,
+}
+
+impl UpdateMethod {
+    fn rpc_name(&self) -> &'static str {
+        match self {
+            UpdateMethod::Update => "updateidentity",
+            UpdateMethod::Recover => "recoveridentity",
+        }
+    }
+}
+
+/// Fetches the current definition of an identity, applies the changes that were set and sends it
+/// back with either `updateidentity` or `recoveridentity`.
+#[derive(Debug)]
+pub struct IdentityUpdateBuilder {
+    method: UpdateMethod,
+    testnet: bool,
+    name: String,
+    minimum_signatures: Option<u8>,
+    addresses: Option<Vec<Address>>,
+    revocation_authority: Option<String>,
+    recovery_authority: Option<String>,
+    content_map: Option<Value>,
+}
+
+impl IdentityUpdateBuilder {
+    fn new(name: &str, method: UpdateMethod) -> Self {
+        IdentityUpdateBuilder {
+            method,
+            testnet: false,
+            name: String::from(name),
+            minimum_signatures: None,
+            addresses: None,
+            revocation_authority: None,
+            recovery_authority: None,
+            content_map: None,
+        }
+    }
+
+    pub fn testnet(&mut self, testnet: bool) -> &mut Self {
+        self.testnet = testnet;
+
+        self
+    }
+
+    pub fn minimum_signatures(&mut self, s: u8) -> &mut Self {
+        self.minimum_signatures = Some(s);
+
+        self
+    }
+
+    /// Replaces all the primary addresses of the identity with the addresses that are added here.
+    pub fn add_address(&mut self, address: &Address) -> &mut Self {
+        match self.addresses.as_mut() {
+            Some(vec) => {
+                vec.push(address.clone());
+            }
+            None => {
+                self.addresses = Some(vec![address.clone()]);
+            }
+        }
+
+        self
+    }
+
+    pub fn revocation_authority(&mut self, s: &str) -> &mut Self {
+        self.revocation_authority = Some(String::from(s));
+
+        self
+    }
+
+    pub fn recovery_authority(&mut self, s: &str) -> &mut Self {
+        self.recovery_authority = Some(String::from(s));
+
+        self
+    }
+
+    pub fn with_content_map(&mut self, cm: Value) -> &mut Self {
+        self.content_map = Some(cm);
+
+        self
+    }
+
+    pub fn validate(&mut self) -> Result<&mut Self, IdentityError> {
+        if let Some(addresses) = self.addresses.as_ref() {
+            if addresses.is_empty() {
+                return Err(ErrorKind::Other(String::from(
+                    "no primary address given, need at least 1",
+                ))
+                .into());
+            }
+
+            if let Some(min_sigs) = self.minimum_signatures {
+                if min_sigs > addresses.len() as u8 {
+                    return Err(ErrorKind::Other(String::from(
+                        "Cannot have more minimum_signatures than there are primary addresses",
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        for authority in [&self.revocation_authority, &self.recovery_authority]
+            .into_iter()
+            .flatten()
+        {
+            validate_authority(authority)?;
+        }
+
+        Ok(self)
+    }
+
+    pub async fn update(&self) -> Result<Txid, IdentityError> {
+        let client = match self.testnet {
+            true => Client::chain("vrsctest", vrsc_rpc::Auth::ConfigFile, None),
+            false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
+        }?;
+
+        let identity = self.updated_definition(&client)?;
+        debug!("{}: {}", self.method.rpc_name(), &identity);
+
+        let txid = client.call(self.method.rpc_name(), &[identity])?;
+        info!(
+            "identity `{}` has been updated (txid: {})",
+            &self.name, &txid
+        );
+
+        Ok(txid)
+    }
+
+    /// The identity definition as it would be sent, without sending it. The marketplace uses this
+    /// to describe what an identity looks like after it has been swapped.
+    pub fn definition(&self) -> Result<Value, IdentityError> {
+        let client = match self.testnet {
+            true => Client::chain("vrsctest", vrsc_rpc::Auth::ConfigFile, None),
+            false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
+        }?;
+
+        self.updated_definition(&client)
+    }
+
+    /// Like `update`, but instead of sending the transaction it is returned as hex, signed with the
+    /// keys that the wallet has. The other primary addresses can then add their signatures.
+    pub async fn partially_signed(&self) -> Result<String, IdentityError> {
+        let client = match self.testnet {
+            true => Client::chain("vrsctest", vrsc_rpc::Auth::ConfigFile, None),
+            false => Client::chain("VRSC", vrsc_rpc::Auth::ConfigFile, None),
+        }?;
+
+        let identity = self.updated_definition(&client)?;
+        debug!("{} (returntx): {}", self.method.rpc_name(), &identity);
+
+        let hex = client.call(self.method.rpc_name(), &[identity, json!(true)])?;
+
+        Ok(hex)
+    }
+
+    fn updated_definition(&self, client: &Client) -> Result<Value, IdentityError> {
+        let current: Value = client.call("getidentity", &[json!(self.name)])?;
+        let mut identity = current
+            .get("identity")
+            .cloned()
+            .ok_or_else(|| ErrorKind::Other(format!("identity {} not found", &self.name)))?;
+
+        if let Some(addresses) = self.addresses.as_ref() {
+            identity["primaryaddresses"] = json!(addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect::<Vec<_>>());
+            // a minimum that is higher than the new amount of addresses would make the update invalid
+            identity["minimumsignatures"] = json!(self.minimum_signatures.unwrap_or(1));
+        } else if let Some(min_sigs) = self.minimum_signatures {
+            identity["minimumsignatures"] = json!(min_sigs);
+        }
+
+        if let Some(authority) = self.revocation_authority.as_ref() {
+            identity["revocationauthority"] = json!(authority);
+        }
+
+        if let Some(authority) = self.recovery_authority.as_ref() {
+            identity["recoveryauthority"] = json!(authority);
+        }
+
+        if let Some(content_map) = self.content_map.as_ref() {
+            identity["contentmap"] = content_map.clone();
+        }
+
+        Ok(identity)
+    }
+}
+
+// An authority is either a fully qualified identity name (ends with `@`) or an i-address.
+fn validate_authority(authority: &str) -> Result<(), IdentityError> {
+    let is_name = authority.len() > 1 && authority.ends_with('@');
+    let is_i_address = authority.len() == 34 && authority.starts_with('i');
+
+    if !is_name && !is_i_address {
+        return Err(ErrorKind::Other(format!(
+            "`{}` is not a valid authority, expected an identity name ending in @ or an i-address",
+            authority
+        ))
+        .into());
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, Display)]
@@ -309,7 +859,7 @@ mod tests {
 
     use vrsc_rpc::{json::vrsc::Address, jsonrpc::serde_json::json};
 
-    use super::Identity;
+    use super::{same_transaction, updates_identity, Identity, VaultState};
 
     #[test]
     fn good_contentmap() {
@@ -334,4 +884,92 @@ mod tests {
             .validate()
             .is_err());
     }
+
+    #[test]
+    fn good_authorities() {
+        let mut identity_builder = Identity::builder();
+
+        assert!(identity_builder
+            .name("test")
+            .add_address(&Address::from_str("RP1sexQNvjGPohJkK9JnuPDH7V7NboycGj").unwrap())
+            .revocation_authority("geckotest@")
+            .recovery_authority("iJhCezBExJHvtyH3fGhNnt2NhU4Ztkf2yq")
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn bad_authorities() {
+        let mut identity_builder = Identity::builder();
+
+        assert!(identity_builder
+            .name("test")
+            .add_address(&Address::from_str("RP1sexQNvjGPohJkK9JnuPDH7V7NboycGj").unwrap())
+            .revocation_authority("geckotest")
+            .validate()
+            .is_err());
+
+        assert!(Identity::update("1.geckotest@")
+            .recovery_authority("@")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn update_more_signatures_than_addresses() {
+        assert!(Identity::update("1.geckotest@")
+            .add_address(&Address::from_str("RP1sexQNvjGPohJkK9JnuPDH7V7NboycGj").unwrap())
+            .minimum_signatures(2)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn cosigned_transactions() {
+        let partial = json!({
+            "txid": "aa",
+            "locktime": 0,
+            "expiryheight": 1020,
+            "vin": [{ "txid": "11", "vout": 0, "scriptSig": { "hex": "" } }],
+            "vout": [{
+                "valueSat": 0,
+                "scriptPubKey": {
+                    "hex": "beef",
+                    "identityprimary": { "identityaddress": "iJhCezBExJHvtyH3fGhNnt2NhU4Ztkf2yq" }
+                }
+            }]
+        });
+        let mut signed = partial.clone();
+        signed["txid"] = json!("bb");
+        signed["vin"][0]["scriptSig"]["hex"] = json!("4730");
+        let mut other = signed.clone();
+        other["vout"][0]["scriptPubKey"]["hex"] = json!("dead");
+
+        assert!(same_transaction(&partial, &signed));
+        assert!(!same_transaction(&partial, &other));
+        assert!(!same_transaction(&json!({}), &json!({})));
+        assert!(updates_identity(
+            &signed,
+            "iJhCezBExJHvtyH3fGhNnt2NhU4Ztkf2yq"
+        ));
+        assert!(!updates_identity(
+            &signed,
+            "iBDkVJqik6BrtcDBQfFygffiYzTMy6EuhU"
+        ));
+    }
+
+    #[test]
+    fn vault_states() {
+        assert_eq!(VaultState::from_identity(0, 0, 100), VaultState::Unlocked);
+        assert_eq!(VaultState::from_identity(0, 90, 100), VaultState::Unlocked);
+        assert_eq!(
+            VaultState::from_identity(2, 1440, 100),
+            VaultState::Locked { delay: 1440 }
+        );
+        assert_eq!(
+            VaultState::from_identity(0, 160, 100),
+            VaultState::Unlocking { blocks_left: 60 }
+        );
+        assert!(!VaultState::from_identity(0, 0, 100).is_locked());
+    }
 }
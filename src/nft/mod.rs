@@ -8,11 +8,15 @@ Is concerned with:
 
 pub(crate) mod art;
 pub(crate) mod arweave;
-mod config;
+pub(crate) mod config;
+pub(crate) mod formats;
 pub(crate) mod identity;
 pub(crate) mod metadata;
 
-use crate::configuration::Settings;
+use crate::{
+    configuration::{IdentitySettings, Settings},
+    lifecycle::{EventBus, MintStage, NftEvent},
+};
 use identity::Identity;
 use serde_json::{json, Value};
 use std::{
@@ -44,24 +48,19 @@ pub struct VerusNFT {
     pub uploaded_image_tx_hash: Option<String>,
     pub uploaded_metadata_tx_hash: Option<String>,
     pub identity: Option<Identity>,
+    // uploads and registrations are logged and simulated, see `ApplicationSettings::dry_run`
+    pub dry_run: bool,
 }
 
 // an enum to keep track of where the process is, updating along the way
 // store the enum in case of failure somewhere, so catch it in the callee (events.rs) and write status to database
 impl VerusNFT {
-    pub async fn generate(user_id: u64, app_config: &Settings) -> Self {
-        let asset_config_location = format!("{}/config.json", &app_config.application.assets_dir);
-        let client = match app_config.application.testnet {
-            true => Client::chain("vrsctest", Auth::ConfigFile, None).expect("a verus client"),
-            false => Client::default(),
-        };
-
-        let address = client.get_new_address().unwrap();
-
-        let mut nft_builder = Self {
+    /// A gecko that is about to be minted for a Discord user and controlled by `vrsc_address`.
+    pub fn new(user_id: u64, sequence: u64, vrsc_address: Address, app_config: &Settings) -> Self {
+        Self {
             user_id,
-            vrsc_address: address,
-            sequence: app_config.application.sequence_start,
+            vrsc_address,
+            sequence,
             edition: app_config.application.series.clone(),
             rarity: 0.0,
             generated_metadata_path: None,
@@ -69,31 +68,122 @@ impl VerusNFT {
             uploaded_image_tx_hash: None,
             uploaded_metadata_tx_hash: None,
             identity: None,
+            dry_run: app_config.application.dry_run,
+        }
+    }
+
+    /// The first stage whose result is missing, in the order the pipeline runs them. A pipeline
+    /// that stopped at a failed stage resumes from there.
+    pub fn next_stage(&self) -> Option<MintStage> {
+        if self.generated_metadata_path.is_none() {
+            Some(MintStage::Metadata)
+        } else if self.generated_image_path.is_none() {
+            Some(MintStage::Art)
+        } else if self.uploaded_image_tx_hash.is_none() {
+            Some(MintStage::ImageUpload)
+        } else if self.uploaded_metadata_tx_hash.is_none() {
+            Some(MintStage::MetadataUpload)
+        } else if self.identity.is_none() {
+            Some(MintStage::Identity)
+        } else {
+            None
+        }
+    }
+
+    /// Runs a stage of the pipeline and publishes whether it succeeded.
+    pub async fn run_stage(
+        &mut self,
+        stage: MintStage,
+        app_config: &Settings,
+        bus: &EventBus,
+    ) -> bool {
+        let application = &app_config.application;
+
+        match stage {
+            MintStage::Metadata => {
+                let asset_config_location = format!("{}/config.json", &application.assets_dir);
+                self.generate_metadata(&asset_config_location, &application.output_dir)
+                    .await
+            }
+            MintStage::Art => {
+                self.generate_art(&application.assets_dir, &application.output_dir)
+                    .await
+            }
+            MintStage::ImageUpload => {
+                self.arweave_image_upload(&application.ardrive_wallet_location)
+                    .await
+            }
+            MintStage::MetadataUpload => {
+                self.update_metadata().await;
+                self.arweave_metadata_upload(&application.ardrive_wallet_location)
+                    .await
+            }
+            MintStage::Identity => {
+                self.create_identity(application.testnet, &app_config.identity)
+                    .await
+            }
+        }
+
+        self.stage_done(bus, stage)
+    }
+
+    /// Waits until the identity and the metadata are confirmed and announces the new gecko. When the
+    /// identity was created by an earlier run of the pipeline, or simulated, there is nothing to
+    /// wait for.
+    pub async fn finish(&self, app_config: &Settings, bus: &EventBus) {
+        if self.dry_run {
+            info!(
+                "dry run: not waiting for {}.{}@ to be confirmed",
+                self.sequence, &self.edition
+            );
+        } else if self.identity.is_some() {
+            let client = match app_config.application.testnet {
+                true => Client::chain("vrsctest", Auth::ConfigFile, None).expect("a verus client"),
+                false => Client::default(),
+            };
+
+            self.is_confirmed(&client).await;
+        }
+
+        bus.publish(self.minted());
+    }
+
+    /// The event of the finished mint.
+    pub fn minted(&self) -> NftEvent {
+        NftEvent::Minted {
+            gecko_number: self.sequence as i64,
+            identity: format!("{}.{}@", self.sequence, &self.edition),
+            discord_user_id: self.user_id,
+            owner: self.vrsc_address.to_string(),
+            metadata_tx: self.uploaded_metadata_tx_hash.clone(),
+            image_tx: self.uploaded_image_tx_hash.clone(),
+        }
+    }
+
+    // The stages log their own errors and leave their result empty when they fail.
+    fn stage_done(&self, bus: &EventBus, stage: MintStage) -> bool {
+        let done = match stage {
+            MintStage::Metadata => self.generated_metadata_path.is_some(),
+            MintStage::Art => self.generated_image_path.is_some(),
+            MintStage::ImageUpload => self.uploaded_image_tx_hash.is_some(),
+            MintStage::MetadataUpload => self.uploaded_metadata_tx_hash.is_some(),
+            MintStage::Identity => self.identity.is_some(),
         };
+        let gecko_number = self.sequence as i64;
+
+        match done {
+            true => bus.publish(NftEvent::StageCompleted {
+                gecko_number,
+                stage,
+            }),
+            false => bus.publish(NftEvent::MintFailed {
+                gecko_number,
+                discord_user_id: self.user_id,
+                stage,
+            }),
+        }
 
-        nft_builder
-            .generate_metadata(&asset_config_location, &app_config.application.output_dir)
-            .await;
-        nft_builder
-            .generate_art(
-                &app_config.application.assets_dir,
-                &app_config.application.output_dir,
-            )
-            .await;
-        nft_builder
-            .arweave_image_upload(&app_config.application.ardrive_wallet_location)
-            .await;
-        nft_builder.update_metadata().await;
-        nft_builder
-            .arweave_metadata_upload(&app_config.application.ardrive_wallet_location)
-            .await;
-        nft_builder
-            .create_identity(app_config.application.testnet)
-            .await;
-
-        nft_builder.is_confirmed(&client).await;
-
-        nft_builder
+        done
     }
 
     /// Generates the metadata for the user that just entered and stores it locally.
@@ -128,21 +218,22 @@ impl VerusNFT {
 
     async fn arweave_image_upload(&mut self, arweave_wallet_location: &str) {
         if let Some(path) = self.generated_image_path.clone() {
+            let identity = format!("{}.{}@", self.sequence, &self.edition);
+            let tags = vec![
+                ("Content-Type", "image/png"),
+                ("identity", identity.as_str()),
+            ];
+            if self.dry_run {
+                self.uploaded_image_tx_hash = Some(arweave::simulate_upload(&path, &tags));
+                return;
+            }
+
             let mut arweave_tx =
                 arweave::ArweaveTransaction::new(Path::new(arweave_wallet_location)).await;
 
             debug!("arweave instance created");
 
-            match arweave_tx
-                .upload(
-                    &path,
-                    vec![
-                        ("Content-Type", "image/png"),
-                        ("identity", &format!("{}.{}@", self.sequence, &self.edition)),
-                    ],
-                )
-                .await
-            {
+            match arweave_tx.upload(&path, tags).await {
                 Ok(tx_hash) => {
                     self.uploaded_image_tx_hash = Some(tx_hash);
                 }
@@ -189,21 +280,22 @@ impl VerusNFT {
 
     async fn arweave_metadata_upload(&mut self, arweave_wallet_location: &str) {
         if let Some(path) = self.generated_metadata_path.clone() {
+            let vdxfid = format!("{}.{}@", self.sequence, &self.edition); //TODO set actual vdxfid
+            let tags = vec![
+                ("Content-Type", "application/json"),
+                ("vdxfid", vdxfid.as_str()),
+            ];
+            if self.dry_run {
+                self.uploaded_metadata_tx_hash = Some(arweave::simulate_upload(&path, &tags));
+                return;
+            }
+
             let mut arweave_tx =
                 arweave::ArweaveTransaction::new(Path::new(arweave_wallet_location)).await;
 
             debug!("arweave instance created");
 
-            match arweave_tx
-                .upload(
-                    &path,
-                    vec![
-                        ("Content-Type", "application/json"),
-                        ("vdxfid", &format!("{}.{}@", self.sequence, &self.edition)), //TODO set actual vdxfid
-                    ],
-                )
-                .await
-            {
+            match arweave_tx.upload(&path, tags).await {
                 Ok(tx_hash) => {
                     self.uploaded_metadata_tx_hash = Some(tx_hash);
                 }
@@ -216,7 +308,7 @@ impl VerusNFT {
         }
     }
 
-    async fn create_identity(&mut self, testnet: bool) {
+    async fn create_identity(&mut self, testnet: bool, identity_settings: &IdentitySettings) {
         debug!(
             "creating identity with primary address: {}",
             &self.vrsc_address
@@ -238,6 +330,14 @@ impl VerusNFT {
 
         let mut identity_builder = Identity::builder();
 
+        if let Some(authority) = identity_settings.revocation_authority.as_ref() {
+            identity_builder.revocation_authority(authority);
+        }
+
+        if let Some(authority) = identity_settings.recovery_authority.as_ref() {
+            identity_builder.recovery_authority(authority);
+        }
+
         if let Err(e) = identity_builder
             .name(&format!("{}", self.sequence))
             .on_currency_name(&self.edition)
@@ -250,12 +350,15 @@ impl VerusNFT {
             return;
         }
 
-        let identity_result = identity_builder.create_identity().await;
+        let identity_result = match self.dry_run {
+            true => Ok(identity_builder.simulate_identity()),
+            false => identity_builder.create_identity().await,
+        };
         match identity_result {
             Ok(identity) => {
                 info!(
-                    "identity `{}` has been created! (txid: {})",
-                    identity.name_commitment.namereservation.name, identity.registration_txid
+                    "identity `{}.{}@` has been created! (txid: {})",
+                    self.sequence, &self.edition, identity.registration_txid
                 );
                 self.identity = Some(identity);
             }
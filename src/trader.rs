@@ -1,3 +1,502 @@
 /*
 on-chain marketplace wrapper functions
 */
+// Geckos are swapped for currency with the offers of the Verus marketplace. An ask offers the
+// identity of a gecko for currency, a bid offers currency for the identity. Taking either one
+// swaps both sides atomically in a single transaction.
+// Only the offers themselves are handled here; who made them and what they did to the ownership of
+// a gecko is kept track of by `bot::market`.
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{error::Error, str::FromStr};
+use tracing::{debug, info};
+use vrsc_rpc::{bitcoin::Txid, json::vrsc::Address, Auth, Client, RpcApi};
+
+use crate::nft::identity::{Identity, IdentityError};
+
+const SATS_PER_COIN: u64 = 100_000_000;
+// the ids of the native currencies, which `getoffers` uses instead of their names
+const VRSC_ID: &str = "i5w5MuNik5NtLcYmNzcvaoixooEebB6MGV";
+const VRSCTEST_ID: &str = "iJhCezBExJHvtyH3fGhNnt2NhU4Ztkf2yq";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OfferSide {
+    // the identity is offered for currency
+    Ask,
+    // currency is offered for the identity
+    Bid,
+}
+
+impl OfferSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OfferSide::Ask => "ask",
+            OfferSide::Bid => "bid",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub txid: String,
+    pub side: OfferSide,
+    pub identity: String,
+    // in satoshis of `currency`
+    pub price: u64,
+    pub currency: String,
+    // the primary address the identity goes to, if the offer says so (bids do)
+    pub new_owner: Option<String>,
+    pub expiry_height: Option<u64>,
+}
+
+/// All open offers on a single gecko.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Listing {
+    pub identity: String,
+    pub asks: Vec<Offer>,
+    pub bids: Vec<Offer>,
+}
+
+impl Listing {
+    fn from_offers(identity: &str, offers: Vec<Offer>) -> Self {
+        let (asks, bids) = offers
+            .into_iter()
+            .partition(|offer| offer.side == OfferSide::Ask);
+
+        Listing {
+            identity: identity.to_string(),
+            asks,
+            bids,
+        }
+    }
+
+    pub fn lowest_ask(&self) -> Option<&Offer> {
+        self.asks.iter().min_by_key(|offer| offer.price)
+    }
+
+    pub fn highest_bid(&self) -> Option<&Offer> {
+        self.bids.iter().max_by_key(|offer| offer.price)
+    }
+
+    pub fn find(&self, txid: &str) -> Option<&Offer> {
+        self.asks
+            .iter()
+            .chain(self.bids.iter())
+            .find(|offer| offer.txid == txid)
+    }
+}
+
+pub struct Marketplace {
+    client: Client,
+    testnet: bool,
+    currency: String,
+}
+
+impl Marketplace {
+    pub fn new(testnet: bool) -> Result<Self, TraderError> {
+        let client = match testnet {
+            true => Client::chain("vrsctest", Auth::ConfigFile, None),
+            false => Client::chain("VRSC", Auth::ConfigFile, None),
+        }?;
+
+        Ok(Marketplace {
+            client,
+            testnet,
+            currency: native_currency(testnet).to_string(),
+        })
+    }
+
+    // all prices are in the native currency of the chain
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    pub fn block_height(&self) -> Result<u64, TraderError> {
+        Ok(self.client.call("getblockcount", &[])?)
+    }
+
+    /// Offers a gecko for sale. The wallet of the daemon must be able to update the gecko on its
+    /// own. The price is paid to `seller_address`.
+    pub fn create_ask(
+        &self,
+        identity_name: &str,
+        price: u64,
+        seller_address: &Address,
+        expiry_height: Option<u64>,
+    ) -> Result<Txid, TraderError> {
+        let mut offer = json!({
+            "changeaddress": seller_address.to_string(),
+            "offer": { "identity": identity_name },
+            "for": {
+                "address": seller_address.to_string(),
+                "currency": self.currency,
+                "amount": to_coins(price),
+            },
+        });
+
+        if let Some(height) = expiry_height {
+            offer["expiryheight"] = json!(height);
+        }
+
+        self.make_offer(&seller_address.to_string(), offer)
+    }
+
+    /// Offers currency for a gecko. The price is paid from `buyer_address`, which also becomes the
+    /// only primary address of the gecko when the bid is taken.
+    pub fn create_bid(
+        &self,
+        identity_name: &str,
+        price: u64,
+        buyer_address: &Address,
+        expiry_height: Option<u64>,
+    ) -> Result<Txid, TraderError> {
+        let mut offer = json!({
+            "changeaddress": buyer_address.to_string(),
+            "offer": { "currency": self.currency, "amount": to_coins(price) },
+            "for": self.definition_for(identity_name, buyer_address)?,
+        });
+
+        if let Some(height) = expiry_height {
+            offer["expiryheight"] = json!(height);
+        }
+
+        self.make_offer(&buyer_address.to_string(), offer)
+    }
+
+    fn make_offer(&self, from_address: &str, offer: Value) -> Result<Txid, TraderError> {
+        debug!("makeoffer {} {}", from_address, &offer);
+
+        let response: Value = self
+            .client
+            .call("makeoffer", &[json!(from_address), offer])?;
+
+        let txid = response["txid"]
+            .as_str()
+            .and_then(|txid| Txid::from_str(txid).ok())
+            .ok_or_else(|| ErrorKind::UnexpectedResponse(response.to_string()))?;
+        info!("offer created (txid: {})", &txid);
+
+        Ok(txid)
+    }
+
+    /// Finds the open offers on any gecko, not only the ones that were made through the bot.
+    pub fn offers(&self, identity_name: &str) -> Result<Listing, TraderError> {
+        let response: Value = self.client.call(
+            "getoffers",
+            &[json!(identity_name), json!(false), json!(false)],
+        )?;
+        debug!("getoffers {}: {}", identity_name, &response);
+
+        let mut offers = parse_offers(identity_name, &response, self.testnet);
+        for offer in offers.iter_mut() {
+            if is_currency_id(&offer.currency) {
+                offer.currency = self.currency_name(&offer.currency)?;
+            }
+        }
+
+        Ok(Listing::from_offers(identity_name, offers))
+    }
+
+    // The fully qualified name of a currency that an offer only gives the id of.
+    fn currency_name(&self, currency_id: &str) -> Result<String, TraderError> {
+        let definition: Value = self.client.call("getcurrency", &[json!(currency_id)])?;
+
+        definition["fullyqualifiedname"]
+            .as_str()
+            .or_else(|| definition["name"].as_str())
+            .map(String::from)
+            .ok_or_else(|| ErrorKind::UnexpectedResponse(definition.to_string()).into())
+    }
+
+    /// Takes an offer on behalf of `taker_address`. For an ask the taker pays and gets the gecko,
+    /// for a bid the taker delivers the gecko and gets paid.
+    pub fn fill(&self, offer: &Offer, taker_address: &Address) -> Result<Txid, TraderError> {
+        let take = match offer.side {
+            OfferSide::Ask => json!({
+                "txid": offer.txid,
+                "changeaddress": taker_address.to_string(),
+                "deliver": { "currency": offer.currency, "amount": to_coins(offer.price) },
+                "accept": self.definition_for(&offer.identity, taker_address)?,
+            }),
+            OfferSide::Bid => json!({
+                "txid": offer.txid,
+                "changeaddress": taker_address.to_string(),
+                "deliver": offer.identity,
+                "accept": {
+                    "address": taker_address.to_string(),
+                    "currency": offer.currency,
+                    "amount": to_coins(offer.price),
+                },
+            }),
+        };
+        debug!("takeoffer {} {}", taker_address, &take);
+
+        let txid: Txid = self
+            .client
+            .call("takeoffer", &[json!(taker_address.to_string()), take])?;
+        info!("offer {} taken (txid: {})", &offer.txid, &txid);
+
+        Ok(txid)
+    }
+
+    /// The transaction that took an offer, if it is the last update of the gecko: a takeoffer spends
+    /// the offer and updates the identity in the same transaction. A gecko that changed hands in
+    /// any other way was not sold through the offer.
+    pub fn taken_by(
+        &self,
+        offer_txid: &str,
+        identity_name: &str,
+    ) -> Result<Option<String>, TraderError> {
+        let identity: Value = self.client.call("getidentity", &[json!(identity_name)])?;
+        let update_txid = identity["txid"]
+            .as_str()
+            .ok_or_else(|| ErrorKind::UnexpectedResponse(identity.to_string()))?;
+        let update: Value = self
+            .client
+            .call("getrawtransaction", &[json!(update_txid), json!(1)])?;
+
+        Ok(spends(&update, offer_txid).then(|| update_txid.to_string()))
+    }
+
+    /// Closes offers that were made by the wallet of the bot and returns what was offered.
+    pub fn close(&self, txids: &[String]) -> Result<(), TraderError> {
+        let response: Value = self.client.call("closeoffers", &[json!(txids)])?;
+        debug!("closeoffers: {}", response);
+
+        Ok(())
+    }
+
+    fn definition_for(&self, identity_name: &str, owner: &Address) -> Result<Value, TraderError> {
+        let definition = Identity::update(identity_name)
+            .testnet(self.testnet)
+            .add_address(owner)
+            .minimum_signatures(1)
+            .definition()?;
+
+        Ok(definition)
+    }
+}
+
+pub fn native_currency(testnet: bool) -> &'static str {
+    match testnet {
+        true => "VRSCTEST",
+        false => "VRSC",
+    }
+}
+
+fn native_currency_id(testnet: bool) -> &'static str {
+    match testnet {
+        true => VRSCTEST_ID,
+        false => VRSC_ID,
+    }
+}
+
+// Whether `currency` is the i-address of a currency rather than its name.
+fn is_currency_id(currency: &str) -> bool {
+    currency.len() == 34 && currency.starts_with('i') && !currency.contains('.')
+}
+
+pub fn to_coins(sats: u64) -> f64 {
+    sats as f64 / SATS_PER_COIN as f64
+}
+
+pub fn from_coins(coins: f64) -> u64 {
+    (coins * SATS_PER_COIN as f64).round() as u64
+}
+
+pub fn format_amount(sats: u64) -> String {
+    let formatted = format!("{}.{:08}", sats / SATS_PER_COIN, sats % SATS_PER_COIN);
+
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+// `getoffers` groups the offers by what is offered for what. Every offer has an `offer` and an
+// `accept` side, one of which is the identity and the other one the currency. The native currency
+// is named; other currencies can be left as their id.
+fn parse_offers(identity_name: &str, response: &Value, testnet: bool) -> Vec<Offer> {
+    let groups = match response.as_object() {
+        Some(groups) => groups,
+        None => return vec![],
+    };
+
+    groups
+        .values()
+        .filter_map(|group| group.as_array())
+        .flatten()
+        .filter_map(|entry| {
+            let txid = entry["txid"].as_str()?;
+            let (side, identity_side, currency_side) = if is_identity(&entry["offer"]) {
+                (OfferSide::Ask, &entry["offer"], &entry["accept"])
+            } else if is_identity(&entry["accept"]) {
+                (OfferSide::Bid, &entry["accept"], &entry["offer"])
+            } else {
+                return None;
+            };
+            let (mut currency, amount) = currency_amount(currency_side)?;
+            if currency == native_currency_id(testnet) {
+                currency = native_currency(testnet).to_string();
+            }
+
+            Some(Offer {
+                txid: txid.to_string(),
+                side,
+                identity: identity_name.to_string(),
+                price: from_coins(amount),
+                currency,
+                new_owner: identity_side["primaryaddresses"][0]
+                    .as_str()
+                    .map(String::from),
+                expiry_height: entry["blockexpiry"].as_u64(),
+            })
+        })
+        .collect()
+}
+
+// Whether a decoded transaction spends an output of `txid`.
+fn spends(tx: &Value, txid: &str) -> bool {
+    tx["vin"]
+        .as_array()
+        .map(|vin| vin.iter().any(|input| input["txid"].as_str() == Some(txid)))
+        .unwrap_or(false)
+}
+
+fn is_identity(side: &Value) -> bool {
+    side.get("name").is_some() || side.get("identityid").is_some()
+}
+
+// either `{"currency": "VRSC", "amount": 1.0}` or `{"<currencyid>": 1.0}`
+fn currency_amount(side: &Value) -> Option<(String, f64)> {
+    if let (Some(currency), Some(amount)) = (side["currency"].as_str(), side["amount"].as_f64()) {
+        return Some((currency.to_string(), amount));
+    }
+
+    side.as_object()?
+        .iter()
+        .find_map(|(currency, amount)| amount.as_f64().map(|a| (currency.clone(), a)))
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "{}", kind)]
+pub struct TraderError {
+    pub kind: ErrorKind,
+    source: Option<Box<dyn Error + Send + Sync + 'static>>,
+}
+
+#[derive(Debug, Display)]
+pub enum ErrorKind {
+    #[display(fmt = "Something went wrong while sending a request to the verusd RPC.")]
+    VrscRpcError(vrsc_rpc::Error),
+    #[display(fmt = "Unexpected response from the daemon: {}", _0)]
+    UnexpectedResponse(String),
+    IdentityError(IdentityError),
+}
+
+impl Error for TraderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|boxed| boxed.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+impl From<ErrorKind> for TraderError {
+    fn from(kind: ErrorKind) -> Self {
+        TraderError { kind, source: None }
+    }
+}
+
+impl From<vrsc_rpc::Error> for TraderError {
+    fn from(e: vrsc_rpc::Error) -> Self {
+        ErrorKind::VrscRpcError(e).into()
+    }
+}
+
+impl From<IdentityError> for TraderError {
+    fn from(e: IdentityError) -> Self {
+        ErrorKind::IdentityError(e).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts() {
+        assert_eq!(from_coins(1.5), 150_000_000);
+        assert_eq!(from_coins(0.00000001), 1);
+        assert_eq!(to_coins(250_000_000), 2.5);
+        assert_eq!(format_amount(150_000_000), "1.5");
+        assert_eq!(format_amount(200_000_000), "2");
+        assert_eq!(format_amount(1), "0.00000001");
+    }
+
+    #[test]
+    fn parse_asks_and_bids() {
+        let response = json!({
+            "ids_for_currency": [{
+                "offer": { "name": "5.geckotest@", "identityid": "iJhCezBExJHvtyH3fGhNnt2NhU4Ztkf2yq" },
+                "accept": { "iJhCezBExJHvtyH3fGhNnt2NhU4Ztkf2yq": 10.0 },
+                "blockexpiry": 1000,
+                "txid": "aa"
+            }],
+            "currency_for_ids": [{
+                "offer": { "currency": "VRSCTEST", "amount": 2.5 },
+                "accept": { "name": "5", "primaryaddresses": ["RP1sexQNvjGPohJkK9JnuPDH7V7NboycGj"] },
+                "blockexpiry": 1200,
+                "txid": "bb"
+            }, {
+                "offer": { "currency": "VRSCTEST", "amount": 3.0 },
+                "accept": { "name": "5" },
+                "txid": "cc"
+            }]
+        });
+
+        let listing = Listing::from_offers(
+            "5.geckotest@",
+            parse_offers("5.geckotest@", &response, true),
+        );
+
+        assert_eq!(listing.asks.len(), 1);
+        assert_eq!(listing.bids.len(), 2);
+        assert_eq!(listing.lowest_ask().unwrap().price, 1_000_000_000);
+        assert_eq!(listing.highest_bid().unwrap().txid, "cc");
+        assert_eq!(
+            listing.find("bb").unwrap().new_owner.as_deref(),
+            Some("RP1sexQNvjGPohJkK9JnuPDH7V7NboycGj")
+        );
+        assert_eq!(listing.find("bb").unwrap().currency, "VRSCTEST");
+        // the ask names the currency by its id
+        assert_eq!(listing.find("aa").unwrap().currency, "VRSCTEST");
+    }
+
+    #[test]
+    fn currency_ids() {
+        assert!(is_currency_id(VRSC_ID));
+        assert!(is_currency_id(VRSCTEST_ID));
+        assert!(!is_currency_id("VRSCTEST"));
+        assert!(!is_currency_id("geckotest"));
+    }
+
+    #[test]
+    fn takeoffer_spends_the_offer() {
+        let takeoffer = json!({
+            "txid": "dd",
+            "vin": [{ "txid": "aa", "vout": 0 }, { "txid": "ee", "vout": 1 }]
+        });
+
+        assert!(spends(&takeoffer, "aa"));
+        assert!(!spends(&takeoffer, "bb"));
+        assert!(!spends(&json!({ "txid": "ff" }), "aa"));
+    }
+
+    #[test]
+    fn parse_unexpected_response() {
+        assert!(parse_offers("5.geckotest@", &json!(null), true).is_empty());
+        assert!(parse_offers("5.geckotest@", &json!({ "x": [{ "offer": {} }] }), true).is_empty());
+    }
+}